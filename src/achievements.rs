@@ -0,0 +1,287 @@
+use crate::chip8::{parse_number, rom_hash, Field, MachineState};
+use crate::text::{columns, truncate, Canvas, LINE_HEIGHT};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt, fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+const TEXT: [u8; 4] = [0xf2, 0xf2, 0xf2, 0xff];
+const GOLD: [u8; 4] = [0xf5, 0xc5, 0x42, 0xff];
+const BACKGROUND: [u8; 4] = [0x20, 0x1c, 0x30, 0xff];
+const MARGIN: usize = 4;
+/// Updates (at 60 Hz) a notification stays on screen
+const NOTIFICATION_UPDATES: u32 = 180;
+
+/// Comparison operators, longest first so `>=` isn't read as `>`
+const OPERATORS: [(&str, Operator); 6] = [
+    ("==", Operator::Equal),
+    ("!=", Operator::NotEqual),
+    ("<=", Operator::LessOrEqual),
+    (">=", Operator::GreaterOrEqual),
+    ("<", Operator::Less),
+    (">", Operator::Greater),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Comparison {
+    field: Field,
+    operator: Operator,
+    value: usize,
+}
+
+impl Comparison {
+    fn holds(&self, state: &MachineState) -> bool {
+        let actual = state.get(self.field);
+        match self.operator {
+            Operator::Equal => actual == self.value,
+            Operator::NotEqual => actual != self.value,
+            Operator::Less => actual < self.value,
+            Operator::LessOrEqual => actual <= self.value,
+            Operator::Greater => actual > self.value,
+            Operator::GreaterOrEqual => actual >= self.value,
+        }
+    }
+}
+
+/// Comparisons of registers and memory with values, all of which must hold,
+/// e.g. `[0x3F0] >= 10 && V5 == 0`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Condition(Vec<Comparison>);
+
+impl Condition {
+    pub fn holds(&self, state: &MachineState) -> bool {
+        self.0.iter().all(|comparison| comparison.holds(state))
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let comparisons = text
+            .split("&&")
+            .map(|comparison| {
+                let (position, symbol, operator) = (OPERATORS.iter())
+                    .find_map(|&(symbol, operator)| {
+                        Some((comparison.find(symbol)?, symbol, operator))
+                    })
+                    .ok_or_else(|| {
+                        format!("expected FIELD OP VALUE, got `{}`", comparison.trim())
+                    })?;
+                let field = comparison[..position].parse()?;
+                let value = comparison[position + symbol.len()..].trim();
+                let value =
+                    parse_number(value).ok_or_else(|| format!("invalid value `{}`", value))?;
+                Ok(Comparison {
+                    field,
+                    operator,
+                    value,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self(comparisons))
+    }
+}
+
+impl TryFrom<String> for Condition {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+/// A goal for a ROM, unlocked when its condition becomes true.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Achievement {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub when: Condition,
+}
+
+impl fmt::Display for Achievement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.description.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} - {}", self.name, self.description)
+        }
+    }
+}
+
+/// Achievements for each ROM, keyed by its SHA-1 like the database, and the
+/// ones unlocked so far, saved to a file of their own.
+pub struct Achievements {
+    definitions: HashMap<String, Vec<Achievement>>,
+    unlocked: BTreeMap<String, Vec<String>>,
+    unlocked_path: PathBuf,
+    /// SHA-1 of the loaded ROM
+    hash: String,
+    /// Whether each of the loaded ROM's achievements has seen its condition
+    /// false, so one already true when the ROM starts doesn't unlock
+    armed: Vec<bool>,
+}
+
+impl Achievements {
+    /// Reads the achievements in `definitions` and those unlocked in
+    /// `unlocked`, either of which may not exist yet.
+    pub fn load(definitions: &Path, unlocked: &Path) -> Result<Self, Error> {
+        let definitions: HashMap<String, Vec<Achievement>> = read_toml(definitions)?;
+        Ok(Self {
+            definitions: (definitions.into_iter())
+                .map(|(hash, achievements)| (hash.to_lowercase(), achievements))
+                .collect(),
+            unlocked: read_toml(unlocked)?,
+            unlocked_path: unlocked.to_path_buf(),
+            hash: String::new(),
+            armed: vec![],
+        })
+    }
+
+    /// Switches to the achievements of `rom`.
+    pub fn select_rom(&mut self, rom: &[u8]) {
+        self.hash = rom_hash(rom);
+        self.armed = vec![false; self.current().len()];
+        if !self.armed.is_empty() {
+            println!(
+                "[Achievements] {} of {} unlocked",
+                self.unlocked_names().len(),
+                self.current().len()
+            );
+        }
+    }
+
+    /// Checks the loaded ROM's achievements against the machine, returning
+    /// the ones that just unlocked. Call `save` when there are any.
+    pub fn update(&mut self, state: &MachineState) -> Vec<Achievement> {
+        let Some(achievements) = self.definitions.get(&self.hash) else {
+            return vec![];
+        };
+        let names = self.unlocked.get(&self.hash);
+        let mut unlocked = vec![];
+        for (i, achievement) in achievements.iter().enumerate() {
+            if names.is_some_and(|names| names.contains(&achievement.name)) {
+                continue;
+            }
+            let holds = achievement.when.holds(state);
+            if holds && self.armed[i] {
+                unlocked.push(achievement.clone());
+            }
+            self.armed[i] = !holds;
+        }
+        if !unlocked.is_empty() {
+            let names = self.unlocked.entry(self.hash.clone()).or_default();
+            names.extend(unlocked.iter().map(|achievement| achievement.name.clone()));
+        }
+        unlocked
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let source = toml::to_string(&self.unlocked).map_err(Error::other)?;
+        fs::write(
+            &self.unlocked_path,
+            format!(
+                "# Achievements unlocked per ROM, keyed by the SHA-1 of the ROM file\n\n{}",
+                source
+            ),
+        )
+    }
+
+    fn current(&self) -> &[Achievement] {
+        (self.definitions.get(&self.hash))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    fn unlocked_names(&self) -> &[String] {
+        (self.unlocked.get(&self.hash))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+/// Achievements just unlocked, shown one after the other at the bottom of
+/// the window.
+#[derive(Default)]
+pub struct Notifications {
+    queue: VecDeque<Achievement>,
+    /// Updates the first one has been shown for
+    shown: u32,
+}
+
+impl Notifications {
+    pub fn push(&mut self, achievement: Achievement) {
+        self.queue.push_back(achievement);
+    }
+
+    /// Moves on to the next notification once the current one has been
+    /// shown long enough. Call once per update.
+    pub fn tick(&mut self) {
+        if self.queue.is_empty() {
+            return;
+        }
+        self.shown += 1;
+        if self.shown >= NOTIFICATION_UPDATES {
+            self.queue.pop_front();
+            self.shown = 0;
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas) {
+        let Some(achievement) = self.queue.front() else {
+            return;
+        };
+        let columns = columns(canvas.width() - MARGIN * 2);
+        let lines = if achievement.description.is_empty() {
+            2
+        } else {
+            3
+        };
+        let height = MARGIN * 2 + LINE_HEIGHT * lines;
+        let top = canvas.height() - height;
+        canvas.fill_rect(0, top, canvas.width(), height, BACKGROUND);
+
+        let y = top + MARGIN;
+        canvas.draw_text(MARGIN, y, &truncate("Achievement unlocked", columns), GOLD);
+        let y = y + LINE_HEIGHT;
+        canvas.draw_text(MARGIN, y, &truncate(&achievement.name, columns), TEXT);
+        let y = y + LINE_HEIGHT;
+        canvas.draw_text(
+            MARGIN,
+            y,
+            &truncate(&achievement.description, columns),
+            TEXT,
+        );
+    }
+}
+
+/// Parses a TOML file, or returns the default if there's no such file.
+fn read_toml<T: Default + for<'de> Deserialize<'de>>(path: &Path) -> Result<T, Error> {
+    match fs::read_to_string(path) {
+        Ok(source) => toml::from_str(&source).map_err(|err| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), err),
+            )
+        }),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(T::default()),
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,724 @@
+use crate::achievements::{Achievements, Notifications};
+use crate::browser::Browser;
+use crate::captures::Captures;
+use crate::cheats::{Cheat, CheatBook, MemorySearch, SearchFilter};
+use crate::chip8::{
+    get_processed_input, keymap_with_overrides, rom_hash, save_png, AudioBackend, Beeper, Chip8,
+    Display, HookId, Hotkeys, KeyMap, Keys, Palette, RecordingFormat, StackError, SCREEN_HEIGHT,
+    SCREEN_WIDTH,
+};
+use crate::debugger::{
+    print_help, print_memory, print_registers, Console, DebugCommand, Watchpoints,
+};
+use crate::profiler::Profile;
+use crate::roms::{RomLibrary, RomWatcher};
+use crate::scripting::Script;
+use crate::text::Canvas;
+use pixels::Pixels;
+use std::{
+    cell::RefCell,
+    io,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+const VOLUME_STEP: f32 = 0.1;
+/// Memory search results short enough to list
+const SEARCH_LISTED: usize = 16;
+/// Longest an update may spend running frames at unlimited speed, leaving
+/// the rest of the 60 Hz tick for rendering
+const UNLIMITED_BUDGET: Duration = Duration::from_millis(10);
+/// Frame buffer pixels per hires screen pixel, so text overlays can be
+/// sharper than the screen
+pub const BUFFER_SCALE: usize = 2;
+pub const BUFFER_WIDTH: usize = SCREEN_WIDTH * BUFFER_SCALE;
+pub const BUFFER_HEIGHT: usize = SCREEN_HEIGHT * BUFFER_SCALE;
+
+/// How fast the game runs compared to real time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RunSpeed {
+    /// One frame every so many updates
+    Slow(u32),
+    /// So many frames every update
+    Fast(u32),
+    /// As many frames as fit in `UNLIMITED_BUDGET`
+    Unlimited,
+}
+
+const RUN_SPEEDS: [RunSpeed; 6] = [
+    RunSpeed::Slow(4),
+    RunSpeed::Slow(2),
+    RunSpeed::Fast(1),
+    RunSpeed::Fast(2),
+    RunSpeed::Fast(4),
+    RunSpeed::Unlimited,
+];
+
+impl RunSpeed {
+    fn name(&self) -> String {
+        match self {
+            RunSpeed::Slow(updates) => format!("{}x", 1.0 / *updates as f32),
+            RunSpeed::Fast(frames) => format!("{}x", frames),
+            RunSpeed::Unlimited => "unlimited".to_string(),
+        }
+    }
+
+    /// The speed `steps` steps faster (or slower if negative) in
+    /// `RUN_SPEEDS`, stopping at either end.
+    fn step(&self, steps: isize) -> RunSpeed {
+        let i = RUN_SPEEDS
+            .iter()
+            .position(|speed| speed == self)
+            .unwrap_or(2);
+        let i = i.saturating_add_signed(steps).min(RUN_SPEEDS.len() - 1);
+        RUN_SPEEDS[i]
+    }
+}
+
+/// The windowed frontend: runs the interpreter at 60 frames per second and
+/// connects it to the window, keyboard and speakers.
+pub struct App {
+    pub chip8: Chip8,
+    pub pixels: Pixels,
+    pub input: WinitInputHelper,
+    display: Display,
+    audio: Box<dyn AudioBackend>,
+    keymap: KeyMap,
+    /// CHIP-8 keys held on the keyboard
+    keyboard: Keys,
+    captures: Captures,
+    capture_dir: PathBuf,
+    recording_format: RecordingFormat,
+    volume: f32,
+    muted: bool,
+    paused: bool,
+    /// Run one frame even though paused
+    advance_frame: bool,
+    run_speed: RunSpeed,
+    /// Updates since the last frame, when running slowly
+    idle_updates: u32,
+    debug: bool,
+    /// ROM browser, when running from a directory or zip archive
+    browser: Option<Browser>,
+    browsing: bool,
+    /// Whether a ROM has been loaded, as opposed to just a library
+    rom_loaded: bool,
+    /// Palette chosen on the command line, winning over the database
+    palette: Option<Palette>,
+    /// Window title to set, after switching ROMs
+    new_title: Option<String>,
+    /// Escape was used to close the browser rather than to quit
+    escape_handled: bool,
+    /// ROM file to reload when it changes
+    watcher: Option<RomWatcher>,
+    /// Keep the key mapping when the watched ROM is reloaded
+    keep_keys: bool,
+    /// Keep the breakpoints when the watched ROM is reloaded
+    keep_breakpoints: bool,
+    console: Option<Console>,
+    watchpoints: Rc<RefCell<Watchpoints>>,
+    /// The watchpoints' hook, added while any address is watched
+    watch_hook: Option<HookId>,
+    profile: Option<Profile>,
+    cheat_book: Option<CheatBook>,
+    /// The cheats for the loaded ROM, applied before every frame
+    cheats: Vec<Cheat>,
+    search: Option<MemorySearch>,
+    achievements: Option<Achievements>,
+    notifications: Notifications,
+    script: Option<Script>,
+}
+
+impl App {
+    pub fn new(
+        chip8: Chip8,
+        pixels: Pixels,
+        display: Display,
+        audio: Box<dyn AudioBackend>,
+        captures: Captures,
+    ) -> Self {
+        let keymap = keymap_with_overrides(&chip8.rom_config().keys);
+        Self {
+            chip8,
+            pixels,
+            input: WinitInputHelper::new(),
+            display,
+            audio,
+            keymap,
+            keyboard: [false; 16],
+            captures,
+            capture_dir: PathBuf::from("."),
+            recording_format: RecordingFormat::default(),
+            volume: Beeper::default().volume,
+            muted: false,
+            paused: false,
+            advance_frame: false,
+            run_speed: RunSpeed::Fast(1),
+            idle_updates: 0,
+            debug: false,
+            browser: None,
+            browsing: false,
+            rom_loaded: true,
+            palette: None,
+            new_title: None,
+            escape_handled: false,
+            watcher: None,
+            keep_keys: false,
+            keep_breakpoints: false,
+            console: None,
+            watchpoints: Rc::default(),
+            watch_hook: None,
+            profile: None,
+            cheat_book: None,
+            cheats: vec![],
+            search: None,
+            achievements: None,
+            notifications: Notifications::default(),
+            script: None,
+        }
+    }
+
+    /// Takes debugger commands from the terminal.
+    pub fn set_console(&mut self, console: Console) {
+        self.console = Some(console);
+    }
+
+    /// Applies the cheats saved for each ROM, and saves those added from the
+    /// debugger.
+    pub fn set_cheat_book(&mut self, book: CheatBook) {
+        self.cheat_book = Some(book);
+        self.load_cheats();
+    }
+
+    /// Checks the achievements of each ROM every frame, announcing and saving
+    /// those unlocked.
+    pub fn set_achievements(&mut self, mut achievements: Achievements) {
+        achievements.select_rom(self.chip8.rom());
+        self.achievements = Some(achievements);
+    }
+
+    /// Profiles the game until exit, saving the profile in `finish`.
+    pub fn set_profile(&mut self, profile: Profile) {
+        self.profile = Some(profile);
+    }
+
+    /// Runs `script` before every frame, stopping it on its first error.
+    pub fn set_script(&mut self, script: Script) {
+        self.script = Some(script);
+    }
+
+    /// Reloads the ROM whenever `watcher` sees it change. With `keep_keys`,
+    /// the key mapping stays as it is rather than being looked up again for
+    /// the new ROM, and with `keep_breakpoints` the breakpoints stay rather
+    /// than being cleared.
+    pub fn set_watcher(&mut self, watcher: RomWatcher, keep_keys: bool, keep_breakpoints: bool) {
+        self.watcher = Some(watcher);
+        self.keep_keys = keep_keys;
+        self.keep_breakpoints = keep_breakpoints;
+    }
+
+    /// Shows the ROM browser (F1) for the ROMs in `library`. With
+    /// `rom_loaded` false, nothing runs until one of them is picked.
+    pub fn set_library(&mut self, library: RomLibrary, rom_loaded: bool) {
+        self.browser = Some(Browser::new(library));
+        self.browsing = true;
+        self.rom_loaded = rom_loaded;
+    }
+
+    /// The palette chosen on the command line, used for every ROM instead of
+    /// the one the database picks.
+    pub fn set_palette(&mut self, palette: Option<Palette>) {
+        self.palette = palette;
+    }
+
+    /// The window title, if it changed since the last call.
+    pub fn take_title(&mut self) -> Option<String> {
+        self.new_title.take()
+    }
+
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    /// Beeper volume from 0 to 1, changed with - and +.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+        self.update_volume();
+    }
+
+    /// Where screenshots and recordings started with hotkeys are saved.
+    pub fn set_capture_dir(&mut self, dir: PathBuf, format: RecordingFormat) {
+        self.capture_dir = dir;
+        self.recording_format = format;
+    }
+
+    pub fn update(&mut self) {
+        self.notifications.tick();
+        self.reload_changed_rom();
+        self.run_debug_commands();
+        let advance_frame = std::mem::take(&mut self.advance_frame);
+        if self.browsing || !self.rom_loaded {
+            return;
+        }
+        if self.paused {
+            if advance_frame {
+                self.run_frame();
+            }
+            return;
+        }
+
+        let frames = match self.run_speed {
+            RunSpeed::Slow(updates) => {
+                self.idle_updates = (self.idle_updates + 1) % updates;
+                (self.idle_updates == 0) as u32
+            }
+            RunSpeed::Fast(frames) => frames,
+            RunSpeed::Unlimited => u32::MAX,
+        };
+        let start = Instant::now();
+        for _ in 0..frames {
+            if !self.run_frame() {
+                break;
+            }
+            if self.run_speed == RunSpeed::Unlimited && start.elapsed() > UNLIMITED_BUDGET {
+                break;
+            }
+        }
+
+        // One frame of sound per update, whatever the speed
+        self.audio.push_frame(self.chip8.sound_frame());
+    }
+
+    /// Runs a frame and captures it. Returns false if it stopped at a
+    /// breakpoint, a watched write or on an error, pausing the game.
+    fn run_frame(&mut self) -> bool {
+        for cheat in &self.cheats {
+            cheat.apply(&mut self.chip8);
+        }
+        self.run_script();
+        let result = self.chip8.run_frame(self.debug);
+        let breakpoint = self.chip8.take_breakpoint_hit();
+        if let Some(address) = breakpoint {
+            self.paused = true;
+            println!("[Debugger] Breakpoint at 0x{:03X}", address);
+            print_registers(&self.chip8);
+        }
+        self.check_achievements();
+        let watched = self.report_watch_hits();
+        if let Err(err) = &result {
+            self.report_error(err);
+        }
+        self.display.push(self.chip8.state().screen);
+        if self.captures.is_active() {
+            let frame = self.display.frame_rgba(self.chip8.state().screen);
+            self.captures.push(&frame, self.chip8.sound_frame());
+        }
+        breakpoint.is_none() && !watched && result.is_ok()
+    }
+
+    fn run_script(&mut self) {
+        let Some(script) = &mut self.script else {
+            return;
+        };
+        if let Err(err) = script.run_frame(&mut self.chip8, self.keyboard) {
+            eprintln!("[Script] {}, stopping the script", err);
+            if let Some(script) = self.script.take() {
+                script.stop(&mut self.chip8);
+            }
+        }
+    }
+
+    fn check_achievements(&mut self) {
+        let Some(achievements) = &mut self.achievements else {
+            return;
+        };
+        let unlocked = achievements.update(&self.chip8.state());
+        if unlocked.is_empty() {
+            return;
+        }
+        for achievement in unlocked {
+            println!("[Achievements] Unlocked {}", achievement);
+            self.notifications.push(achievement);
+        }
+        if let Err(err) = achievements.save() {
+            eprintln!("[Achievements] Failed to save: {}", err);
+        }
+    }
+
+    /// Pauses if a watched address was written, after the frame that wrote
+    /// it. Returns whether it did.
+    fn report_watch_hits(&mut self) -> bool {
+        let hits = self.watchpoints.borrow_mut().take_hits();
+        for hit in &hits {
+            println!(
+                "[Debugger] 0x{:03X} = 0x{:02X}, written at 0x{:03X}",
+                hit.address, hit.value, hit.pc
+            );
+        }
+        if hits.is_empty() {
+            return false;
+        }
+        self.paused = true;
+        print_registers(&self.chip8);
+        true
+    }
+
+    /// Watches `address`, or stops watching it, adding the watchpoints' hook
+    /// only while there is something to watch.
+    fn toggle_watchpoint(&mut self, address: usize) {
+        let watched = self.watchpoints.borrow_mut().toggle(address);
+        if watched {
+            println!("[Debugger] Watching 0x{:03X}", address);
+        } else {
+            println!("[Debugger] Stopped watching 0x{:03X}", address);
+        }
+        let any = !self.watchpoints.borrow().addresses().is_empty();
+        match self.watch_hook {
+            None if any => {
+                let hook = Box::new(Rc::clone(&self.watchpoints));
+                self.watch_hook = Some(self.chip8.add_hook(hook));
+            }
+            Some(id) if !any => {
+                self.chip8.remove_hook(id);
+                self.watch_hook = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Pauses on an error the ROM ran into, until it is reset.
+    fn report_error(&mut self, err: &StackError) {
+        self.paused = true;
+        eprintln!("[Error] {}, pausing", err);
+        print_registers(&self.chip8);
+    }
+
+    pub fn render(&mut self) {
+        let frame = self.display.frame_rgba(self.chip8.state().screen);
+        let buffer = self.pixels.frame_mut();
+        for (y, row) in buffer.chunks_exact_mut(BUFFER_WIDTH * 4).enumerate() {
+            let screen_row = y / BUFFER_SCALE * SCREEN_WIDTH;
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let i = (screen_row + x / BUFFER_SCALE) * 4;
+                pixel.copy_from_slice(&frame[i..i + 4]);
+            }
+        }
+
+        let mut canvas = Canvas::new(buffer, BUFFER_WIDTH);
+        if let Some(browser) = self.browser.as_mut().filter(|_| self.browsing) {
+            browser.draw(&mut canvas);
+        } else {
+            if let Some(script) = &self.script {
+                script.draw(&mut canvas);
+            }
+            self.notifications.draw(&mut canvas);
+        }
+    }
+
+    pub fn update_controls(&mut self) {
+        self.escape_handled = false;
+        if self.browsing {
+            self.update_browser();
+            return;
+        }
+
+        let (keys, hotkeys) = get_processed_input(&self.input, &self.keymap);
+        self.chip8.set_keys(keys);
+        self.keyboard = keys;
+
+        let Hotkeys {
+            toggle_pause,
+            cycle_palette,
+            cycle_filter,
+            screenshot,
+            toggle_recording,
+            toggle_mute,
+            volume_down,
+            volume_up,
+            toggle_browser,
+            reset,
+            hard_reset,
+            slow_down,
+            speed_up,
+            normal_speed,
+            advance_frame,
+        } = hotkeys;
+        if toggle_browser && self.browser.is_some() {
+            self.browsing = true;
+        }
+        if toggle_pause {
+            self.paused = !self.paused;
+        }
+        if advance_frame {
+            self.paused = true;
+            self.advance_frame = true;
+        }
+        if slow_down || speed_up || normal_speed {
+            self.run_speed = match (normal_speed, speed_up) {
+                (true, _) => RunSpeed::Fast(1),
+                (false, true) => self.run_speed.step(1),
+                (false, false) => self.run_speed.step(-1),
+            };
+            self.idle_updates = 0;
+            println!("[Speed] {}", self.run_speed.name());
+        }
+        if reset || hard_reset {
+            self.reset(hard_reset);
+        }
+        if cycle_palette {
+            self.display.palette = self.display.palette.next();
+            println!("[Display] Palette: {}", self.display.palette.name);
+        }
+        if cycle_filter {
+            self.display.filter = self.display.filter.next();
+            println!("[Display] Filter: {}", self.display.filter.name());
+        }
+        if screenshot {
+            let path = self.capture_path("png");
+            if let Err(err) = self.save_screenshot(&path) {
+                eprintln!("[Capture] Failed to save {}: {}", path.display(), err);
+            }
+        }
+        if toggle_recording {
+            if self.captures.is_recording() {
+                self.captures.stop_recording();
+            } else {
+                let path = self.capture_path(self.recording_format.extension());
+                self.captures.start_recording(path, self.recording_format);
+            }
+        }
+        if toggle_mute {
+            self.muted = !self.muted;
+            self.update_volume();
+            println!("[Audio] {}", if self.muted { "Muted" } else { "Unmuted" });
+        }
+        if volume_down || volume_up {
+            let step = if volume_up { VOLUME_STEP } else { -VOLUME_STEP };
+            self.muted = false;
+            self.set_volume(self.volume + step);
+            println!("[Audio] Volume: {:.0}%", self.volume * 100.0);
+        }
+    }
+
+    pub fn should_close(&self) -> bool {
+        let esc_pressed = self.input.key_pressed(VirtualKeyCode::Escape) && !self.escape_handled;
+        esc_pressed || self.input.close_requested()
+    }
+
+    pub fn save_screenshot(&self, path: &Path) -> Result<(), io::Error> {
+        save_png(path, &self.display.frame_rgba(self.chip8.state().screen))?;
+        println!("[Capture] Screenshot saved to {}", path.display());
+        Ok(())
+    }
+
+    /// Saves the recordings in progress; call before exiting.
+    pub fn finish(&mut self) {
+        self.captures.finish();
+        self.audio.finish();
+        if let Some(profile) = self.profile.take() {
+            if let Err(err) = profile.finish(&mut self.chip8) {
+                eprintln!("[Profile] Failed to save the profile: {}", err);
+            }
+        }
+    }
+
+    fn update_browser(&mut self) {
+        let Some(browser) = &mut self.browser else {
+            return;
+        };
+        self.chip8.set_keys([false; 16]);
+
+        let close = self.input.key_pressed(VirtualKeyCode::Escape)
+            || self.input.key_pressed(VirtualKeyCode::F1);
+        if close && self.rom_loaded {
+            self.browsing = false;
+            self.escape_handled = true;
+            return;
+        }
+
+        if !browser.handle_input(&self.input) {
+            return;
+        }
+        let title = browser.selected_title().to_string();
+        let rom = browser.load_selected();
+        match rom.and_then(|rom| self.switch_rom(&rom, false)) {
+            Ok(()) => {
+                self.new_title = Some(format!("CHIP-8 - {}", title));
+                self.browsing = false;
+                self.rom_loaded = true;
+            }
+            Err(err) => eprintln!("[I/O] Failed to load {}: {}", title, err),
+        }
+    }
+
+    fn reset(&mut self, hard: bool) {
+        if hard {
+            self.chip8.hard_reset();
+            println!("[Reset] Restarted the ROM with memory refilled");
+        } else {
+            self.chip8.reset();
+            println!("[Reset] Restarted the ROM");
+        }
+    }
+
+    fn run_debug_commands(&mut self) {
+        let Some(console) = &self.console else {
+            return;
+        };
+        for command in console.poll() {
+            match command {
+                DebugCommand::Reset => self.reset(false),
+                DebugCommand::HardReset => self.reset(true),
+                DebugCommand::Break(Some(address)) => {
+                    if self.chip8.toggle_breakpoint(address) {
+                        println!("[Debugger] Breakpoint set at 0x{:03X}", address);
+                    } else {
+                        println!("[Debugger] Breakpoint removed at 0x{:03X}", address);
+                    }
+                }
+                DebugCommand::Break(None) => {
+                    let addresses: Vec<String> = self
+                        .chip8
+                        .breakpoints()
+                        .iter()
+                        .map(|address| format!("0x{:03X}", address))
+                        .collect();
+                    println!("[Debugger] Breakpoints: {}", addresses.join(" "));
+                }
+                DebugCommand::Watch(Some(address)) => self.toggle_watchpoint(address),
+                DebugCommand::Watch(None) => {
+                    let addresses: Vec<String> = (self.watchpoints.borrow().addresses().iter())
+                        .map(|address| format!("0x{:03X}", address))
+                        .collect();
+                    println!("[Debugger] Watching: {}", addresses.join(" "));
+                }
+                DebugCommand::Step(count) => {
+                    self.paused = true;
+                    let result = (0..count).try_for_each(|_| self.chip8.step(self.debug));
+                    let watched = self.report_watch_hits();
+                    match result {
+                        Ok(()) if !watched => print_registers(&self.chip8),
+                        Ok(()) => {}
+                        Err(err) => self.report_error(&err),
+                    }
+                }
+                DebugCommand::Continue => self.paused = false,
+                DebugCommand::Pause => {
+                    self.paused = true;
+                    print_registers(&self.chip8);
+                }
+                DebugCommand::Regs => print_registers(&self.chip8),
+                DebugCommand::Set(field, value) => {
+                    self.chip8.set(field, value);
+                    println!("[Debugger] {} = 0x{:X}", field, value);
+                }
+                DebugCommand::Search(filter) => self.search_memory(filter),
+                DebugCommand::Cheat(Some(cheat)) => {
+                    self.edit_cheats(Some(cheat), cheat.address);
+                    println!("[Cheats] Holding {}", cheat);
+                }
+                DebugCommand::Cheat(None) => {
+                    let codes: Vec<String> = self.cheats.iter().map(Cheat::to_string).collect();
+                    println!("[Cheats] {}", codes.join(" "));
+                }
+                DebugCommand::Uncheat(address) => self.edit_cheats(None, address),
+                DebugCommand::Memory(address, length) => print_memory(&self.chip8, address, length),
+                DebugCommand::Help => print_help(),
+            }
+        }
+    }
+
+    fn reload_changed_rom(&mut self) {
+        let Some(rom) = self.watcher.as_mut().and_then(RomWatcher::poll) else {
+            return;
+        };
+        let path = self.watcher.as_ref().unwrap().path().display().to_string();
+        match self.switch_rom(&rom, self.keep_keys) {
+            Ok(()) => {
+                // The code they were on has likely moved
+                if !self.keep_breakpoints {
+                    self.chip8.clear_breakpoints();
+                }
+                println!("[I/O] Reloaded {}", path);
+            }
+            Err(err) => eprintln!("[I/O] Failed to reload {}: {}", path, err),
+        }
+    }
+
+    /// Resets the machine and loads `rom`, with its key mapping (unless
+    /// `keep_keys`) and palette from the database.
+    fn switch_rom(&mut self, rom: &[u8], keep_keys: bool) -> Result<(), io::Error> {
+        self.chip8.load_rom(rom)?;
+        if !keep_keys {
+            self.keymap = keymap_with_overrides(&self.chip8.rom_config().keys);
+        }
+        self.display.palette = self.chip8.rom_config().choose_palette(self.palette);
+        self.load_cheats();
+        if let Some(achievements) = &mut self.achievements {
+            achievements.select_rom(rom);
+        }
+        Ok(())
+    }
+
+    fn load_cheats(&mut self) {
+        let Some(book) = &self.cheat_book else {
+            return;
+        };
+        self.cheats = book.cheats(&rom_hash(self.chip8.rom())).to_vec();
+        if !self.cheats.is_empty() {
+            let codes: Vec<String> = self.cheats.iter().map(Cheat::to_string).collect();
+            println!("[Cheats] Applying {}", codes.join(" "));
+        }
+    }
+
+    /// Adds or removes a cheat for the loaded ROM, saving the cheat book.
+    fn edit_cheats(&mut self, cheat: Option<Cheat>, address: usize) {
+        let Some(book) = &mut self.cheat_book else {
+            return;
+        };
+        let hash = rom_hash(self.chip8.rom());
+        let result = match cheat {
+            Some(cheat) => book.add(&hash, cheat).map(|()| true),
+            None => book.remove(&hash, address),
+        };
+        match result {
+            Ok(true) => {}
+            Ok(false) => println!("[Cheats] No cheat on 0x{:03X}", address),
+            Err(err) => eprintln!("[Cheats] Failed to save cheats: {}", err),
+        }
+        self.cheats = book.cheats(&hash).to_vec();
+    }
+
+    /// Narrows down the memory search, printing the addresses left.
+    fn search_memory(&mut self, filter: SearchFilter) {
+        let memory = self.chip8.state().memory;
+        let search = self.search.get_or_insert_with(|| MemorySearch::new(memory));
+        search.filter(memory, filter);
+        let candidates = search.candidates();
+        println!("[Cheats] {} addresses match", candidates.len());
+        if candidates.len() <= SEARCH_LISTED {
+            let matches: Vec<String> = (candidates.iter())
+                .map(|&address| format!("0x{:03X}=0x{:02X}", address, memory[address]))
+                .collect();
+            println!("{}", matches.join(" "));
+        }
+    }
+
+    fn update_volume(&mut self) {
+        let volume = if self.muted { 0.0 } else { self.volume };
+        self.audio.set_volume(volume);
+    }
+
+    /// Timestamped file name in the capture directory.
+    fn capture_path(&self, extension: &str) -> PathBuf {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis())
+            .unwrap_or_default();
+        self.capture_dir
+            .join(format!("chip8-{}.{}", millis, extension))
+    }
+}
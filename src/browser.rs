@@ -0,0 +1,107 @@
+use crate::app::BUFFER_HEIGHT;
+use crate::roms::RomLibrary;
+use crate::text::{columns, truncate, Canvas, LINE_HEIGHT};
+use std::io;
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+const TEXT: [u8; 4] = [0xf2, 0xf2, 0xf2, 0xff];
+const FAINT: [u8; 4] = [0x99, 0x99, 0x99, 0xff];
+const HIGHLIGHT: [u8; 4] = [0x5e, 0x48, 0xe8, 0xff];
+const MARGIN: usize = 4;
+
+/// In-window list of the ROMs in a library, drawn over the game.
+pub struct Browser {
+    library: RomLibrary,
+    selected: usize,
+    /// First entry shown
+    scroll: usize,
+}
+
+impl Browser {
+    pub fn new(library: RomLibrary) -> Self {
+        Self {
+            library,
+            selected: 0,
+            scroll: 0,
+        }
+    }
+
+    /// Moves the selection with the arrow keys, Page Up/Down, Home and End.
+    /// Returns whether Enter was pressed to load the selected entry.
+    pub fn handle_input(&mut self, input: &WinitInputHelper) -> bool {
+        let last = self.library.entries().len() - 1;
+        let page = Self::visible_rows();
+        if input.key_pressed(VirtualKeyCode::Up) {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if input.key_pressed(VirtualKeyCode::Down) {
+            self.selected = (self.selected + 1).min(last);
+        }
+        if input.key_pressed(VirtualKeyCode::PageUp) {
+            self.selected = self.selected.saturating_sub(page);
+        }
+        if input.key_pressed(VirtualKeyCode::PageDown) {
+            self.selected = (self.selected + page).min(last);
+        }
+        if input.key_pressed(VirtualKeyCode::Home) {
+            self.selected = 0;
+        }
+        if input.key_pressed(VirtualKeyCode::End) {
+            self.selected = last;
+        }
+
+        input.key_pressed(VirtualKeyCode::Return) || input.key_pressed(VirtualKeyCode::NumpadEnter)
+    }
+
+    pub fn load_selected(&mut self) -> Result<Vec<u8>, io::Error> {
+        self.library.load(self.selected)
+    }
+
+    /// Title of the selected entry, or its file name if it isn't known.
+    pub fn selected_title(&self) -> &str {
+        let entry = &self.library.entries()[self.selected];
+        entry.title.as_deref().unwrap_or(&entry.name)
+    }
+
+    pub fn draw(&mut self, canvas: &mut Canvas) {
+        canvas.dim(0.8);
+        let columns = columns(canvas.width() - MARGIN * 2);
+        canvas.draw_text(MARGIN, MARGIN, &truncate("Load ROM", columns), TEXT);
+
+        let rows = Self::visible_rows();
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows;
+        }
+
+        let top = MARGIN + LINE_HEIGHT * 2;
+        let entries = self.library.entries().iter().enumerate();
+        for (row, (i, entry)) in entries.skip(self.scroll).take(rows).enumerate() {
+            let y = top + row * LINE_HEIGHT;
+            if i == self.selected {
+                canvas.fill_rect(0, y - 1, canvas.width(), LINE_HEIGHT, HIGHLIGHT);
+            }
+            let label = match &entry.title {
+                Some(title) => title.as_str(),
+                None => entry.name.as_str(),
+            };
+            let color = if entry.title.is_some() { TEXT } else { FAINT };
+            canvas.draw_text(MARGIN, y, &truncate(label, columns), color);
+        }
+
+        let footer = format!(
+            "{}/{}  Enter: play  Esc: back",
+            self.selected + 1,
+            self.library.entries().len()
+        );
+        let y = canvas.height() - MARGIN - LINE_HEIGHT + 2;
+        canvas.draw_text(MARGIN, y, &truncate(&footer, columns), FAINT);
+    }
+
+    /// Entries that fit between the header and the footer.
+    fn visible_rows() -> usize {
+        (BUFFER_HEIGHT - MARGIN * 2 - LINE_HEIGHT * 3) / LINE_HEIGHT
+    }
+}
@@ -0,0 +1,87 @@
+use crate::chip8::{AvRecorder, Beeper, Recorder, RecordingFormat, SoundFrame};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Recordings in progress, fed one emulated frame at a time by both the
+/// windowed and the headless runners.
+#[derive(Default)]
+pub struct Captures {
+    recorder: Option<Recorder>,
+    av_recorder: Option<AvRecorder>,
+}
+
+impl Captures {
+    pub fn is_active(&self) -> bool {
+        self.recorder.is_some() || self.av_recorder.is_some()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Records every frame as an animation until `stop_recording` is called.
+    pub fn start_recording(&mut self, path: PathBuf, format: RecordingFormat) {
+        self.stop_recording();
+        match Recorder::new(path.clone(), format) {
+            Ok(recorder) => {
+                println!("[Capture] Recording to {}", path.display());
+                self.recorder = Some(recorder);
+            }
+            Err(err) => eprintln!("[Capture] Failed to record to {}: {}", path.display(), err),
+        }
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            let path = recorder.path().to_path_buf();
+            match recorder.save() {
+                Ok(()) => println!("[Capture] Recording saved to {}", path.display()),
+                Err(err) => eprintln!("[Capture] Failed to save {}: {}", path.display(), err),
+            }
+        }
+    }
+
+    /// Records audio and video to `<base>.wav` and `<base>.y4m`.
+    pub fn start_av_recording(&mut self, base: &Path, beeper: Beeper) -> Result<(), io::Error> {
+        self.stop_av_recording();
+        self.av_recorder = Some(AvRecorder::new(base, beeper)?);
+        println!(
+            "[Capture] Recording audio and video to {}.*",
+            base.display()
+        );
+        Ok(())
+    }
+
+    pub fn stop_av_recording(&mut self) {
+        if let Some(av_recorder) = self.av_recorder.take() {
+            let base = av_recorder.base().to_path_buf();
+            match av_recorder.finish() {
+                Ok(()) => println!("[Capture] Audio and video saved to {}.*", base.display()),
+                Err(err) => eprintln!("[Capture] Failed to save {}.*: {}", base.display(), err),
+            }
+        }
+    }
+
+    /// Appends a frame (RGBA screen) to every recording in progress.
+    pub fn push(&mut self, frame: &[u8], sound: &SoundFrame) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(err) = recorder.push(frame) {
+                eprintln!("[Capture] Stopping recording: {}", err);
+                self.recorder = None;
+            }
+        }
+        if let Some(av_recorder) = &mut self.av_recorder {
+            if let Err(err) = av_recorder.push(frame, sound) {
+                eprintln!("[Capture] Stopping audio and video recording: {}", err);
+                self.av_recorder = None;
+            }
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.stop_recording();
+        self.stop_av_recording();
+    }
+}
@@ -0,0 +1,214 @@
+use crate::chip8::{parse_number, Chip8, Field};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// A byte of memory held at a value, written as `ADDR=VALUE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cheat {
+    pub address: usize,
+    pub value: u8,
+}
+
+impl Cheat {
+    /// Writes the value, undoing whatever the game did to it.
+    pub fn apply(&self, chip8: &mut Chip8) {
+        chip8.set(Field::Memory(self.address), self.value as usize);
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x{:03X}=0x{:02X}", self.address, self.value)
+    }
+}
+
+impl FromStr for Cheat {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (address, value) = text
+            .split_once('=')
+            .ok_or_else(|| format!("expected ADDR=VALUE, got `{}`", text))?;
+        let (address, value) = (address.trim(), value.trim());
+        let address = parse_number(address)
+            .filter(|&address| address <= Field::ProgramCounter.max())
+            .ok_or_else(|| format!("invalid address `{}`", address))?;
+        let value = parse_number(value)
+            .and_then(|value| u8::try_from(value).ok())
+            .ok_or_else(|| format!("invalid value `{}`", value))?;
+        Ok(Self { address, value })
+    }
+}
+
+impl TryFrom<String> for Cheat {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<Cheat> for String {
+    fn from(cheat: Cheat) -> Self {
+        cheat.to_string()
+    }
+}
+
+/// Cheat codes saved per ROM in a TOML file, keyed by the SHA-1 of the ROM
+/// like the database.
+pub struct CheatBook {
+    path: PathBuf,
+    entries: BTreeMap<String, Vec<Cheat>>,
+}
+
+impl CheatBook {
+    /// Reads the cheat codes in `path`, if it exists yet.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let entries = match fs::read_to_string(path) {
+            Ok(source) => {
+                toml::from_str(&source).map_err(|err| Error::new(ErrorKind::InvalidData, err))?
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub fn cheats(&self, hash: &str) -> &[Cheat] {
+        self.entries
+            .get(hash)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Adds a cheat for the ROM, replacing any on the same address, and saves
+    /// the file.
+    pub fn add(&mut self, hash: &str, cheat: Cheat) -> Result<(), Error> {
+        let cheats = self.entries.entry(hash.to_string()).or_default();
+        cheats.retain(|other| other.address != cheat.address);
+        cheats.push(cheat);
+        cheats.sort_by_key(|cheat| cheat.address);
+        self.save()
+    }
+
+    /// Removes the ROM's cheat on `address` and saves the file. Returns
+    /// whether there was one.
+    pub fn remove(&mut self, hash: &str, address: usize) -> Result<bool, Error> {
+        let Some(cheats) = self.entries.get_mut(hash) else {
+            return Ok(false);
+        };
+        let count = cheats.len();
+        cheats.retain(|cheat| cheat.address != address);
+        let removed = cheats.len() < count;
+        if cheats.is_empty() {
+            self.entries.remove(hash);
+        }
+        self.save()?;
+        Ok(removed)
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let source = toml::to_string(&self.entries).map_err(Error::other)?;
+        fs::write(
+            &self.path,
+            format!(
+                "# Cheat codes per ROM, keyed by the SHA-1 of the ROM file\n\n{}",
+                source
+            ),
+        )
+    }
+}
+
+/// How a search narrows down the candidate addresses, comparing memory
+/// with the last snapshot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchFilter {
+    /// Start over with every address
+    New,
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Decreased,
+    Increased,
+}
+
+impl FromStr for SearchFilter {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut words = text.split_whitespace();
+        let name = words.next().unwrap_or("new").to_ascii_lowercase();
+        let filter = match name.as_str() {
+            "new" => SearchFilter::New,
+            "eq" | "=" => {
+                let value = words.next().ok_or("expected a value")?;
+                let value = parse_number(value)
+                    .and_then(|value| u8::try_from(value).ok())
+                    .ok_or_else(|| format!("invalid value `{}`", value))?;
+                SearchFilter::Equal(value)
+            }
+            "changed" => SearchFilter::Changed,
+            "same" | "unchanged" => SearchFilter::Unchanged,
+            "down" | "decreased" => SearchFilter::Decreased,
+            "up" | "increased" => SearchFilter::Increased,
+            _ => {
+                return Err(format!(
+                    "unknown search `{}` (expected new, eq, changed, same, down or up)",
+                    name
+                ))
+            }
+        };
+        Ok(filter)
+    }
+}
+
+/// Narrows down where a game keeps a value, such as the number of lives, by
+/// comparing snapshots of memory as it changes.
+pub struct MemorySearch {
+    snapshot: Vec<u8>,
+    candidates: Vec<usize>,
+}
+
+impl MemorySearch {
+    pub fn new(memory: &[u8]) -> Self {
+        Self {
+            snapshot: memory.to_vec(),
+            candidates: (0..memory.len()).collect(),
+        }
+    }
+
+    /// Keeps the candidates that match `filter`, then takes a new snapshot.
+    pub fn filter(&mut self, memory: &[u8], filter: SearchFilter) {
+        if filter == SearchFilter::New {
+            *self = Self::new(memory);
+            return;
+        }
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|&address| {
+            let (old, new) = (snapshot[address], memory[address]);
+            match filter {
+                SearchFilter::New => true,
+                SearchFilter::Equal(value) => new == value,
+                SearchFilter::Changed => new != old,
+                SearchFilter::Unchanged => new == old,
+                SearchFilter::Decreased => new < old,
+                SearchFilter::Increased => new > old,
+            }
+        });
+        self.snapshot = memory.to_vec();
+    }
+
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+}
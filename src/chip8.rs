@@ -1,213 +1,769 @@
+mod assembler;
 mod audio;
+mod av;
+mod capture;
+mod database;
+mod disassembler;
+mod display;
+mod filter;
 mod font;
+mod hooks;
 mod input;
 mod instruction;
+mod memory;
+mod palette;
+mod quirks;
+mod sound;
+mod stack;
+mod state;
+#[cfg(test)]
+mod tests;
+mod timing;
 
+pub use self::{
+    assembler::{assemble, parse_number},
+    audio::{open_backend, AudioBackend, AudioOutput, Beeper, Waveform},
+    av::AvRecorder,
+    capture::{matches_png, save_image, save_png, Recorder, RecordingFormat},
+    database::{rom_hash, Database, RomConfig},
+    disassembler::{detect_platform, disassemble, histogram},
+    display::Display,
+    filter::DisplayFilter,
+    hooks::{Hook, HookId},
+    input::{get_processed_input, keymap_with_overrides, Hotkeys, KeyMap, Keys},
+    memory::MemoryFill,
+    palette::Palette,
+    quirks::{Platform, QuirkOverrides},
+    sound::{AudioPattern, SoundFrame},
+    stack::{StackError, DEFAULT_STACK_DEPTH},
+    state::{Field, MachineState},
+    timing::Timing,
+};
 use self::{
-    audio::Audio,
-    font::FONT,
-    input::{get_processed_input, Keys},
+    font::{BIG_FONT, FONT},
+    hooks::Hooks,
     instruction::Instruction,
+    quirks::Quirks,
+    stack::Stack,
 };
-use pixels::Pixels;
-use rand::{rngs::ThreadRng, Rng};
-use std::fs;
-use winit::event::VirtualKeyCode;
-use winit_input_helper::WinitInputHelper;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::BTreeSet;
 
-pub type Screen = [[bool; SCREEN_WIDTH]; SCREEN_HEIGHT];
+/// Pixels as bitmasks of the planes lit at each position: bit 0 for plane
+/// 1 (the only one outside XO-CHIP) and bit 1 for plane 2
+pub type Screen = [[u8; SCREEN_WIDTH]; SCREEN_HEIGHT];
 type Registers = [u8; 16];
-type RGBA = [u8; 4];
+type Rgba = [u8; 4];
 
-pub const SCREEN_WIDTH: usize = 64;
-pub const SCREEN_HEIGHT: usize = 32;
+/// SUPER-CHIP's 128x64 hires screen; the 64x32 screen is drawn into it
+/// with each pixel doubled
+pub const SCREEN_WIDTH: usize = 128;
+pub const SCREEN_HEIGHT: usize = 64;
+/// The CHIP-8 screen, in its own pixels
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
 const MEMORY_SIZE: usize = 0x1000; // 4KB
+const PROGRAM_START: usize = 0x200;
+/// Bitplanes of the XO-CHIP screen
+const PLANES: usize = 2;
+/// XO-CHIP's pitch at reset, playing audio patterns at 4000 samples a second
+const DEFAULT_PITCH: u8 = 64;
 
-const COLOR_FG: RGBA = [0x5e, 0x48, 0xe8, 0xff];
-const COLOR_BG: RGBA = [0x11, 0x11, 0x11, 0xff];
-
+/// The interpreter itself, independent of any window or audio device.
 pub struct Chip8 {
-    pub pixels: Pixels,
-    pub input: WinitInputHelper,
-    pub screen: Screen,
+    screen: Screen,
     memory: [u8; MEMORY_SIZE],
+    /// The loaded ROM, copied back into memory on reset
+    rom: Vec<u8>,
+    memory_fill: MemoryFill,
     pc: usize,
     index: usize,
-    stack: Vec<usize>,
+    stack: Stack,
     dt: u8,
     st: u8,
     v: Registers,
     keys: Keys,
-    rng: ThreadRng,
-    paused: bool,
-    audio: Audio,
+    rng: StdRng,
+    database: Database,
+    config: RomConfig,
+    platform: Platform,
+    quirks: Quirks,
+    speed: u32,
+    /// Bitmask of the planes sprites are drawn to and cleared from
+    planes: u8,
+    /// Whether the 128x64 screen is in use
+    hires: bool,
+    /// SUPER-CHIP's user flags, kept across resets
+    flags: Registers,
+    /// XO-CHIP's audio pattern, once a ROM loads one, and its pitch
+    pattern: Option<[u8; 16]>,
+    pitch: u8,
+    timing: Timing,
+    sound: SoundFrame,
+    /// Settings from the command line, taking precedence over the database
+    platform_override: Option<Platform>,
+    quirk_overrides: QuirkOverrides,
+    speed_override: Option<u32>,
+    /// Cost of the instructions executed so far in the current frame, in
+    /// the units of the timing's frame budget
+    cycle: u32,
+    /// Whether the current frame has started
+    in_frame: bool,
+    breakpoints: BTreeSet<usize>,
+    /// Address of the breakpoint the last frame stopped at
+    breakpoint_hit: Option<usize>,
+    /// Execute the next instruction even if it has a breakpoint, to resume
+    /// after stopping
+    skip_breakpoint: bool,
+    hooks: Hooks,
 }
 
 impl Chip8 {
-    pub fn new(pixels: Pixels) -> Self {
-        let mut memory = [0u8; MEMORY_SIZE];
-
-        // Load font
-        memory[0..FONT.len()].copy_from_slice(&FONT);
-
+    pub fn new(database: Database) -> Self {
         Self {
-            pixels,
-            input: WinitInputHelper::new(),
-            memory,
-            screen: [[false; SCREEN_WIDTH]; SCREEN_HEIGHT],
+            memory: Self::initial_memory(),
+            rom: vec![],
+            memory_fill: MemoryFill::default(),
+            screen: [[0; SCREEN_WIDTH]; SCREEN_HEIGHT],
             pc: 0x200,
             index: 0,
-            stack: vec![],
+            stack: Stack::new(),
             dt: 0,
             st: 0,
             v: [0u8; 16],
             keys: [false; 16],
-            rng: rand::thread_rng(),
-            paused: false,
-            audio: Audio::new(),
+            rng: StdRng::from_entropy(),
+            database,
+            config: RomConfig::default(),
+            platform: Platform::default(),
+            quirks: Quirks::default(),
+            speed: Platform::default().speed(),
+            planes: 1,
+            hires: false,
+            flags: [0; 16],
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+            timing: Timing::default(),
+            sound: SoundFrame::default(),
+            platform_override: None,
+            quirk_overrides: QuirkOverrides::default(),
+            speed_override: None,
+            cycle: 0,
+            in_frame: false,
+            breakpoints: BTreeSet::new(),
+            breakpoint_hit: None,
+            skip_breakpoint: false,
+            hooks: Hooks::default(),
         }
     }
 
-    pub fn load_file(&mut self, filename: &str) -> Result<(), std::io::Error> {
-        const PROGRAM_START: usize = 0x200;
+    /// Memory holding only the fonts.
+    fn initial_memory() -> [u8; MEMORY_SIZE] {
+        let mut memory = [0u8; MEMORY_SIZE];
+        Self::load_fonts(&mut memory);
+        memory
+    }
 
-        let file_data = fs::read(filename)?;
+    /// Copies the small font, then the big one, to the start of memory.
+    fn load_fonts(memory: &mut [u8]) {
+        memory[..FONT.len()].copy_from_slice(&FONT);
+        memory[FONT.len()..FONT.len() + BIG_FONT.len()].copy_from_slice(&BIG_FONT);
+    }
 
+    /// Loads a ROM into a freshly started machine, replacing whatever was
+    /// running before.
+    pub fn load_rom(&mut self, file_data: &[u8]) -> Result<(), std::io::Error> {
         if file_data.len() > MEMORY_SIZE - PROGRAM_START {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
+            return Err(std::io::Error::other(
                 "File is too large to fit into memory",
             ));
         }
 
-        let program_end = PROGRAM_START + file_data.len();
-        self.memory[PROGRAM_START..program_end].copy_from_slice(&file_data);
+        self.rom = file_data.to_vec();
+        self.hard_reset();
 
         println!("[I/O] ROM file loaded");
 
+        let config = self.database.lookup(file_data).cloned();
+        if let Some(title) = config.as_ref().and_then(|config| config.title.as_ref()) {
+            println!("[DB] Recognized ROM: {}", title);
+        }
+        self.apply_config(config.unwrap_or_default());
+
         Ok(())
     }
 
-    pub fn run_cycle(&mut self, debug: bool) {
-        if self.paused {
-            return;
+    /// Restarts the loaded ROM: copies it and the font back into memory and
+    /// clears the registers, stack, timers and screen. The rest of memory is
+    /// left as it is.
+    pub fn reset(&mut self) {
+        Self::load_fonts(&mut self.memory);
+        let program_end = PROGRAM_START + self.rom.len();
+        self.memory[PROGRAM_START..program_end].copy_from_slice(&self.rom);
+        self.screen = [[0; SCREEN_WIDTH]; SCREEN_HEIGHT];
+        self.planes = 1;
+        self.hires = false;
+        self.pattern = None;
+        self.pitch = DEFAULT_PITCH;
+        self.pc = PROGRAM_START;
+        self.index = 0;
+        self.stack.clear();
+        self.dt = 0;
+        self.st = 0;
+        self.v = [0u8; 16];
+        self.sound = SoundFrame::default();
+        self.cycle = 0;
+        self.in_frame = false;
+        self.breakpoint_hit = None;
+        self.skip_breakpoint = false;
+    }
+
+    /// Like `reset`, but first fills all of memory with the memory fill
+    /// pattern.
+    pub fn hard_reset(&mut self) {
+        self.memory_fill.fill(&mut self.memory, &mut self.rng);
+        self.reset();
+    }
+
+    /// Allows `depth` nested calls, keeping the return addresses in memory
+    /// below 0xED0 like the COSMAC VIP if `in_memory`, where there is room
+    /// for `VIP_STACK_DEPTH`.
+    pub fn set_stack(&mut self, depth: usize, in_memory: bool) {
+        self.stack.configure(depth, in_memory);
+    }
+
+    /// What memory outside the font and ROM holds after a hard reset.
+    pub fn set_memory_fill(&mut self, fill: MemoryFill) {
+        self.memory_fill = fill;
+    }
+
+    /// Title of the loaded ROM, if it is in the database.
+    pub fn title(&self) -> Option<&str> {
+        self.config.title.as_deref()
+    }
+
+    /// The loaded ROM, as it was before running.
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// The platform ROMs are run as.
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// Database entry of the loaded ROM, empty if it isn't in the database.
+    pub fn rom_config(&self) -> &RomConfig {
+        &self.config
+    }
+
+    /// Runs ROMs as written for `platform`, whatever the database says.
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform_override = Some(platform);
+        self.apply_config(self.config.clone());
+    }
+
+    /// Quirks to set regardless of the platform and database.
+    pub fn set_quirk_overrides(&mut self, overrides: QuirkOverrides) {
+        self.quirk_overrides = overrides;
+        self.apply_config(self.config.clone());
+    }
+
+    /// Instructions executed per frame, whatever the database says.
+    pub fn set_speed(&mut self, speed: u32) {
+        self.speed_override = Some(speed);
+        self.apply_config(self.config.clone());
+    }
+
+    /// How long instructions take: a fixed number per frame (the speed), or
+    /// as long as on the COSMAC VIP.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+        self.cycle = 0;
+    }
+
+    /// Makes `RND` repeatable.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn apply_config(&mut self, config: RomConfig) {
+        let platform = self
+            .platform_override
+            .or(config.platform)
+            .unwrap_or_default();
+
+        self.platform = platform;
+        self.quirks = platform.quirks();
+        config.quirks.apply(&mut self.quirks);
+        self.quirk_overrides.apply(&mut self.quirks);
+        // The database speed is meant for the database platform
+        let speed = config.speed.filter(|_| self.platform_override.is_none());
+        self.speed = self.speed_override.or(speed).unwrap_or(platform.speed());
+        self.config = config;
+    }
+
+    pub fn set_keys(&mut self, keys: Keys) {
+        self.keys = keys;
+    }
+
+    /// The registers, timers, stack, memory, screen and keys.
+    pub fn state(&self) -> MachineState<'_> {
+        MachineState {
+            v: self.v,
+            index: self.index,
+            pc: self.pc,
+            delay_timer: self.dt,
+            sound_timer: self.st,
+            stack: self.stack.entries(&self.memory),
+            memory: &self.memory,
+            screen: &self.screen,
+            keys: self.keys,
+        }
+    }
+
+    /// Overwrites a register, timer or byte of memory, truncating `value`
+    /// to what the field holds. Registers and addresses that don't exist
+    /// are left alone.
+    pub fn set(&mut self, field: Field, value: usize) {
+        match field {
+            Field::Register(x) => {
+                if let Some(v) = self.v.get_mut(x) {
+                    *v = value as u8;
+                }
+            }
+            Field::Index => self.index = value & field.max(),
+            Field::ProgramCounter => self.pc = value & field.max(),
+            Field::DelayTimer => self.dt = value as u8,
+            Field::SoundTimer => self.st = value as u8,
+            Field::Memory(address) => {
+                if let Some(byte) = self.memory.get_mut(address) {
+                    *byte = value as u8;
+                }
+            }
+        }
+    }
+
+    /// Whether the buzzer is sounding, i.e. the sound timer is running.
+    pub fn sound_active(&self) -> bool {
+        self.st > 0
+    }
+
+    /// When the buzzer was on during the last frame.
+    pub fn sound_frame(&self) -> &SoundFrame {
+        &self.sound
+    }
+
+    /// Adds a hook fired as the interpreter runs, kept across resets and
+    /// ROM changes.
+    pub fn add_hook(&mut self, hook: Box<dyn Hook>) -> HookId {
+        self.hooks.add(hook)
+    }
+
+    pub fn remove_hook(&mut self, id: HookId) -> Option<Box<dyn Hook>> {
+        self.hooks.remove(id)
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Adds a breakpoint at `address`, or removes it if there already is
+    /// one. Returns whether it was added.
+    pub fn toggle_breakpoint(&mut self, address: usize) -> bool {
+        if self.breakpoints.remove(&address) {
+            false
+        } else {
+            self.breakpoints.insert(address)
+        }
+    }
+
+    /// The address of the breakpoint `run_frame` stopped at, if it did.
+    pub fn take_breakpoint_hit(&mut self) -> Option<usize> {
+        self.breakpoint_hit.take()
+    }
+
+    /// Runs the rest of the current 60 Hz frame worth of instructions, then
+    /// ticks the timers. Stops early before an instruction with a
+    /// breakpoint; the next call resumes from there.
+    ///
+    /// Fails on an instruction the stack can't hold, leaving `pc` on it.
+    pub fn run_frame(&mut self, debug: bool) -> Result<(), StackError> {
+        loop {
+            let skip = std::mem::take(&mut self.skip_breakpoint);
+            if !skip && self.breakpoints.contains(&self.pc) {
+                self.breakpoint_hit = Some(self.pc);
+                self.skip_breakpoint = true;
+                return Ok(());
+            }
+            if self.run_step(debug)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Executes a single instruction, ignoring breakpoints, and ticks the
+    /// timers if that ends the frame.
+    pub fn step(&mut self, debug: bool) -> Result<(), StackError> {
+        self.run_step(debug)?;
+        self.skip_breakpoint = true;
+        Ok(())
+    }
+
+    /// Executes an instruction as part of a frame. Returns whether the
+    /// frame is over.
+    fn run_step(&mut self, debug: bool) -> Result<bool, StackError> {
+        if !self.in_frame {
+            self.sound.begin(self.sound_active());
+            self.in_frame = true;
         }
+        let budget = self.timing.frame_budget(self.speed);
+        self.cycle += self.run_cycle(debug)?;
+        // The instruction's effect on the sound lands once it has run
+        let time = self.cycle.min(budget) as f32 / budget as f32;
+        self.sound.record(time, self.sound_active());
 
-        let opcode = (self.memory[self.pc] as u16) << 8 | self.memory[self.pc + 1] as u16;
-        let instruction = Instruction::from_opcode(opcode);
+        if self.cycle < budget {
+            return Ok(false);
+        }
+        let pattern = (self.pattern.filter(|_| self.platform == Platform::XoChip))
+            .map(|bits| AudioPattern::new(bits, self.pitch));
+        self.sound.set_pattern(pattern);
+        // Time an instruction ran past the end of the frame counts against
+        // the next one
+        self.cycle -= budget;
+        self.in_frame = false;
+        self.update_timers();
+        Ok(true)
+    }
+
+    /// Executes the instruction at `pc`, returning its cost.
+    fn run_cycle(&mut self, debug: bool) -> Result<u32, StackError> {
+        let opcode = self.fetch();
+        self.fire_with_state(|hook, state| hook.before_instruction(state, opcode));
+        // Hooks may have moved PC or rewritten the instruction
+        let opcode = self.fetch();
+        let instruction = Instruction::from_opcode(opcode, self.platform);
         let disassemble = instruction.disassemble();
 
         if debug {
             println!("[0x{:04X}] => 0x{:04X} | {}", self.pc, opcode, disassemble);
         }
 
-        self.execute_instruction(instruction);
-        self.update_timers();
-        self.pc += 2;
-
-        self.audio.set_active(self.st > 0);
+        let cost = self.timing.cost(self, &instruction, self.cycle);
+        let address = self.pc;
+        let sounding = self.sound_active();
+        self.execute_instruction(instruction)?;
+        self.pc = self.pc.wrapping_add(2) % MEMORY_SIZE;
+        self.fire_with_state(|hook, state| hook.after_instruction(state, address, opcode));
+        self.fire_sound_change(sounding);
+        Ok(cost)
     }
 
-    pub fn render(&mut self) {
-        let frame = self.pixels.frame_mut();
-        let screen = &self.screen;
-        for (i, frame_pixel) in frame.chunks_exact_mut(4).enumerate() {
-            let x = (i % SCREEN_WIDTH as usize) as usize;
-            let y = (i / SCREEN_WIDTH as usize) as usize;
-            let screen_pixel = screen[y][x];
-
-            let rgba = if screen_pixel { COLOR_FG } else { COLOR_BG };
+    /// The opcode at `pc`, whose second byte wraps around to the start of
+    /// memory if `pc` is the last address.
+    fn fetch(&self) -> u16 {
+        let low = self.memory[(self.pc + 1) % MEMORY_SIZE];
+        (self.memory[self.pc] as u16) << 8 | low as u16
+    }
 
-            frame_pixel.copy_from_slice(&rgba);
+    /// Fires hooks that are given the machine state, which is only built if
+    /// there are any, then makes the writes they asked for.
+    fn fire_with_state(&mut self, mut call: impl FnMut(&mut dyn Hook, &MachineState)) {
+        if self.hooks.is_empty() {
+            return;
+        }
+        let mut hooks = std::mem::take(&mut self.hooks);
+        let state = self.state();
+        hooks.fire(|hook| call(hook, &state));
+        let mut writes = vec![];
+        hooks.fire(|hook| writes.extend(hook.take_writes()));
+        self.hooks = hooks;
+        for (field, value) in writes {
+            self.set(field, value);
         }
     }
 
-    pub fn update_controls(&mut self) {
-        let (keys, toggle_pause) = get_processed_input(&self.input);
-        self.keys = keys;
-        if toggle_pause {
-            self.paused = !self.paused;
+    fn fire_sound_change(&mut self, was_sounding: bool) {
+        let active = self.sound_active();
+        if active != was_sounding {
+            self.hooks.fire(|hook| hook.sound_changed(active));
         }
     }
 
-    pub fn should_close(&self) -> bool {
-        let esc_pressed = self.input.key_pressed(VirtualKeyCode::Escape);
-        esc_pressed || self.input.close_requested()
+    /// Reads the byte at `address`, wrapping around the end of memory like
+    /// every access through I.
+    fn read_memory(&mut self, address: usize) -> u8 {
+        let address = address % MEMORY_SIZE;
+        let value = self.memory[address];
+        self.hooks.fire(|hook| hook.memory_read(address, value));
+        value
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) {
+        let address = address % MEMORY_SIZE;
+        self.memory[address] = value;
+        self.hooks.fire(|hook| hook.memory_write(address, value));
     }
 
+    /// Screen pixels to a pixel in the current resolution, across and down.
+    fn pixel_size(&self) -> usize {
+        if self.hires {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// XORs the sprite at I onto each selected plane: `n` rows of 8 pixels,
+    /// or 16x16 pixels if `n` is 0 outside CHIP-8. With several planes, the
+    /// sprite for each follows the previous one in memory.
+    ///
+    /// The position, in pixels of the current resolution, wraps around the
+    /// screen. Parts of the sprite past the edges are cut off with the
+    /// clipping quirk, or wrap around. VF is set if any lit pixel is turned
+    /// off.
     fn draw_sprite(&mut self, x: usize, y: usize, n: u8) {
+        let (width, height) = if n == 0 && self.platform != Platform::Chip8 {
+            (16, 16)
+        } else {
+            (8, n as usize)
+        };
+        let row_bytes = width / 8;
+        let size = self.pixel_size();
+        let (screen_width, screen_height) = (SCREEN_WIDTH / size, SCREEN_HEIGHT / size);
+        let left = self.v[x] as usize % screen_width;
+        let top = self.v[y] as usize % screen_height;
+        let mut address = self.index;
         let mut collision = false;
 
-        for row in 0..n as usize {
-            let byte = self.memory[self.index + row];
+        for plane in (0..PLANES).map(|plane| 1 << plane) {
+            if self.planes & plane == 0 {
+                continue;
+            }
+            for row in 0..height {
+                let bits = (0..row_bytes).fold(0u16, |bits, byte| {
+                    let address = (address + row * row_bytes + byte) % MEMORY_SIZE;
+                    bits | (self.memory[address] as u16) << (8 * (1 - byte))
+                });
+                let screen_y = top + row;
+                if screen_y >= screen_height && self.quirks.clipping {
+                    break;
+                }
 
-            for col in 0..8usize {
-                if byte & (0x80 >> col) != 0 {
-                    let screen_y = (self.v[y] as usize + row).clamp(0, SCREEN_HEIGHT - 1);
-                    let screen_x = (self.v[x] as usize + col).clamp(0, SCREEN_WIDTH - 1);
-                    let pixel = &mut self.screen[screen_y][screen_x];
-                    *pixel ^= true;
-                    collision = if *pixel { true } else { collision };
+                for col in 0..width {
+                    let screen_x = left + col;
+                    if bits & (0x8000 >> col) == 0
+                        || (screen_x >= screen_width && self.quirks.clipping)
+                    {
+                        continue;
+                    }
+                    let (x, y) = (
+                        screen_x % screen_width * size,
+                        screen_y % screen_height * size,
+                    );
+                    collision |= self.screen[y][x] & plane != 0;
+                    for row in &mut self.screen[y..y + size] {
+                        for pixel in &mut row[x..x + size] {
+                            *pixel ^= plane;
+                        }
+                    }
                 }
             }
+            address += height * row_bytes;
         }
 
         self.v[0xF] = collision as u8;
+        for sprite_address in self.index..address {
+            let sprite_address = sprite_address % MEMORY_SIZE;
+            let value = self.memory[sprite_address];
+            self.hooks
+                .fire(|hook| hook.memory_read(sprite_address, value));
+        }
+        let (x, y) = (self.v[x], self.v[y]);
+        self.hooks
+            .fire(|hook| hook.sprite_drawn(x, y, n, collision));
+    }
+
+    /// Moves the selected planes `dx` pixels right and `dy` down, in pixels
+    /// of the current resolution, filling in blank pixels.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let size = self.pixel_size() as isize;
+        let (dx, dy) = (dx * size, dy * size);
+        let screen = self.screen;
+        for (y, row) in self.screen.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let from_x = x.checked_add_signed(-dx).filter(|&x| x < SCREEN_WIDTH);
+                let from_y = y.checked_add_signed(-dy).filter(|&y| y < SCREEN_HEIGHT);
+                let moved = match (from_x, from_y) {
+                    (Some(x), Some(y)) => screen[y][x],
+                    _ => 0,
+                };
+                *pixel = (*pixel & !self.planes) | (moved & self.planes);
+            }
+        }
+    }
+
+    /// Skips the next instruction if `taken`: both halves of XO-CHIP's four
+    /// byte `F000 NNNN`.
+    fn skip_if(&mut self, taken: bool) {
+        if !taken {
+            return;
+        }
+        self.pc += 2;
+        let next = self.pc + 2;
+        if self.platform == Platform::XoChip
+            && next + 1 < MEMORY_SIZE
+            && self.memory[next] == 0xF0
+            && self.memory[next + 1] == 0x00
+        {
+            self.pc += 2;
+        }
+    }
+
+    /// Switches between the 64x32 and 128x64 screens, clearing both planes
+    /// as XO-CHIP does.
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.screen = [[0; SCREEN_WIDTH]; SCREEN_HEIGHT];
+        self.hooks.fire(|hook| hook.screen_cleared());
+    }
+
+    /// The registers from X to Y, counting down if X is above Y.
+    fn register_range(x: usize, y: usize) -> Vec<usize> {
+        if x <= y {
+            (x..=y).collect()
+        } else {
+            (y..=x).rev().collect()
+        }
+    }
+
+    /// Clears the selected planes.
+    fn clear_screen(&mut self) {
+        for pixel in self.screen.iter_mut().flatten() {
+            *pixel &= !self.planes;
+        }
+        self.hooks.fire(|hook| hook.screen_cleared());
+    }
+
+    /// Sets VX to `value`, then VF to `flag`.
+    fn set_with_flag(&mut self, x: usize, value: u8, flag: bool) {
+        self.v[x] = value;
+        self.v[0xF] = flag as u8;
+    }
+
+    /// The register `8XY6` and `8XYE` shift: VY, or VX with the shifting
+    /// quirk.
+    fn shift_source(&self, x: usize, y: usize) -> u8 {
+        if self.quirks.shifting {
+            self.v[x]
+        } else {
+            self.v[y]
+        }
+    }
+
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
+    }
+
+    fn increment_index(&mut self, x: usize) {
+        if self.quirks.memory {
+            self.index = (self.index + x + 1) % MEMORY_SIZE;
+        }
     }
 
     fn update_timers(&mut self) {
+        let sounding = self.sound_active();
         self.dt -= if self.dt > 0 { 1 } else { 0 };
         self.st -= if self.st > 0 { 1 } else { 0 };
+        self.fire_sound_change(sounding);
     }
 
-    fn execute_instruction(&mut self, instruction: Instruction) {
+    fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), StackError> {
         use Instruction::*;
         match instruction {
-            SYS(_) => todo!(),
-            CLS => self.screen = [[false; SCREEN_WIDTH]; SCREEN_HEIGHT],
-            RET => self.pc = self.stack.pop().unwrap(),
-            JP(nnn) => self.pc = nnn - 2,
+            // Calls to VIP machine code, which later interpreters ignore
+            SYS(_) => {}
+            CLS => self.clear_screen(),
+            RET => {
+                if let Some(slot) = self.stack.top_slot() {
+                    self.read_memory(slot);
+                    self.read_memory(slot + 1);
+                }
+                // A ROM may have overwritten the address with one below 2
+                self.pc = self.stack.pop(self.pc, &self.memory)?.wrapping_sub(2);
+            }
+            JP(nnn) => self.pc = nnn.wrapping_sub(2),
             CALL(nnn) => {
-                self.stack.push(self.pc);
-                self.pc = nnn - 2;
+                self.stack.push(self.pc, self.pc + 2, &mut self.memory)?;
+                if let Some(slot) = self.stack.top_slot() {
+                    let (high, low) = (self.memory[slot], self.memory[slot + 1]);
+                    self.hooks.fire(|hook| hook.memory_write(slot, high));
+                    self.hooks.fire(|hook| hook.memory_write(slot + 1, low));
+                }
+                self.pc = nnn.wrapping_sub(2);
             }
-            SEVxByte(x, kk) => self.pc += if self.v[x] == kk { 2 } else { 0 },
-            SNEVxByte(x, kk) => self.pc += if self.v[x] != kk { 2 } else { 0 },
-            SEVxVy(x, y) => self.pc += if self.v[x] == self.v[y] { 2 } else { 0 },
+            SEVxByte(x, kk) => self.skip_if(self.v[x] == kk),
+            SNEVxByte(x, kk) => self.skip_if(self.v[x] != kk),
+            SEVxVy(x, y) => self.skip_if(self.v[x] == self.v[y]),
             LDVxByte(x, kk) => self.v[x] = kk,
             ADDVxByte(x, kk) => self.v[x] = self.v[x].wrapping_add(kk),
             LDVxVy(x, y) => self.v[x] = self.v[y],
-            ORVxVy(x, y) => self.v[x] = self.v[x] | self.v[y],
-            ANDVxVy(x, y) => self.v[x] = self.v[x] & self.v[y],
-            XORVxVy(x, y) => self.v[x] = self.v[x] ^ self.v[y],
+            ORVxVy(x, y) => {
+                self.v[x] |= self.v[y];
+                self.reset_vf();
+            }
+            ANDVxVy(x, y) => {
+                self.v[x] &= self.v[y];
+                self.reset_vf();
+            }
+            XORVxVy(x, y) => {
+                self.v[x] ^= self.v[y];
+                self.reset_vf();
+            }
+            // VF is written last, so the flag wins when X is F
             ADDVxVy(x, y) => {
-                let result = self.v[x] as u16 + self.v[y] as u16;
-                self.v[0xF] = if result > 255 { 1 } else { 0 };
-                self.v[x] = result as u8;
+                let (result, carry) = self.v[x].overflowing_add(self.v[y]);
+                self.set_with_flag(x, result, carry);
             }
             SUBVxVy(x, y) => {
-                self.v[0xF] = if self.v[x] > self.v[y] { 1 } else { 0 };
-                self.v[x] = self.v[x].wrapping_sub(self.v[y]);
+                let (result, borrow) = self.v[x].overflowing_sub(self.v[y]);
+                self.set_with_flag(x, result, !borrow);
             }
-            SHRVx(x) => {
-                self.v[0xF] = self.v[x] & 0b1;
-                self.v[x] = self.v[x].wrapping_div(2);
+            SHRVxVy(x, y) => {
+                let source = self.shift_source(x, y);
+                self.set_with_flag(x, source >> 1, source & 0x01 != 0);
             }
             SUBNVxVy(x, y) => {
-                self.v[0xF] = if self.v[y] > self.v[x] { 1 } else { 0 };
-                self.v[x] = self.v[y].wrapping_sub(self.v[x]);
+                let (result, borrow) = self.v[y].overflowing_sub(self.v[x]);
+                self.set_with_flag(x, result, !borrow);
             }
-            SHLVx(x) => {
-                self.v[0xF] = self.v[x] & 0b1;
-                self.v[x] = self.v[x].wrapping_mul(2);
+            SHLVxVy(x, y) => {
+                let source = self.shift_source(x, y);
+                self.set_with_flag(x, source << 1, source & 0x80 != 0);
             }
-            SNEVxVy(x, y) => self.pc += if self.v[x] != self.v[y] { 2 } else { 0 },
+            SNEVxVy(x, y) => self.skip_if(self.v[x] != self.v[y]),
             LDI(nnn) => self.index = nnn,
-            JP0(nnn) => self.pc = nnn + self.v[0] as usize,
+            JP0(nnn) => {
+                let offset = if self.quirks.jumping {
+                    self.v[(nnn >> 8) & 0xF]
+                } else {
+                    self.v[0]
+                };
+                // Past the end of memory, the jump wraps around to the start
+                self.pc = (nnn + offset as usize).wrapping_sub(2);
+            }
             RNDVxByte(x, kk) => self.v[x] = self.rng.gen_range(0..=255) & kk,
             DRWVxVyNibble(x, y, n) => self.draw_sprite(x, y, n),
-            SKPVx(x) => self.pc += if self.keys[self.v[x] as usize] { 2 } else { 0 },
-            SKNPVx(x) => self.pc += if !self.keys[self.v[x] as usize] { 2 } else { 0 },
+            // Only the low nibble selects a key, as on the VIP
+            SKPVx(x) => self.skip_if(self.keys[self.v[x] as usize & 0xF]),
+            SKNPVx(x) => self.skip_if(!self.keys[self.v[x] as usize & 0xF]),
             LDVxDT(x) => self.v[x] = self.dt,
             LDVxK(x) => {
                 let mut key_pressed = None;
@@ -224,25 +780,62 @@ impl Chip8 {
             }
             LDDTVx(x) => self.dt = self.v[x],
             LDSTVx(x) => self.st = self.v[x],
-            ADDIVx(x) => self.index += self.v[x] as usize,
+            ADDIVx(x) => self.index = (self.index + self.v[x] as usize) % MEMORY_SIZE,
             LDFVx(x) => self.index = self.v[x] as usize * 5,
             LDBVx(x) => {
                 let value = self.v[x];
-                self.memory[self.index] = value / 100;
-                self.memory[self.index + 1] = (value % 100) / 10;
-                self.memory[self.index + 2] = value % 10;
+                self.write_memory(self.index, value / 100);
+                self.write_memory(self.index + 1, (value % 100) / 10);
+                self.write_memory(self.index + 2, value % 10);
             }
             LDIVx(x) => {
-                for i in 0..=x as usize {
-                    self.memory[self.index + i] = self.v[i];
+                for i in 0..=x {
+                    self.write_memory(self.index + i, self.v[i]);
                 }
+                self.increment_index(x);
             }
             LDVxMem(x) => {
-                for i in 0..=x as usize {
-                    self.v[i] = self.memory[self.index + i];
+                for i in 0..=x {
+                    self.v[i] = self.read_memory(self.index + i);
                 }
+                self.increment_index(x);
             }
+            PLANE(planes) => self.planes = planes & 0b11,
+            SCD(n) => self.scroll(0, n as isize),
+            SCU(n) => self.scroll(0, -(n as isize)),
+            SCR => self.scroll(4, 0),
+            SCL => self.scroll(-4, 0),
+            // Stay on the instruction, so the ROM stops here
+            EXIT => self.pc = self.pc.wrapping_sub(2),
+            LOW => self.set_hires(false),
+            HIGH => self.set_hires(true),
+            LDHFVx(x) => self.index = FONT.len() + (self.v[x] as usize & 0xF) * 10,
+            LDRVx(x) => self.flags[..=x].copy_from_slice(&self.v[..=x]),
+            LDVxR(x) => self.v[..=x].copy_from_slice(&self.flags[..=x]),
+            SAVEVxVy(x, y) => {
+                for (i, register) in Self::register_range(x, y).into_iter().enumerate() {
+                    self.write_memory(self.index + i, self.v[register]);
+                }
+            }
+            LOADVxVy(x, y) => {
+                for (i, register) in Self::register_range(x, y).into_iter().enumerate() {
+                    self.v[register] = self.read_memory(self.index + i);
+                }
+            }
+            LDILong => {
+                // Memory is 4 KB here, so the address wraps around it
+                let high = self.read_memory(self.pc + 2);
+                let low = self.read_memory(self.pc + 3);
+                self.index = u16::from_be_bytes([high, low]) as usize % MEMORY_SIZE;
+                self.pc += 2;
+            }
+            AUDIO => {
+                let pattern = std::array::from_fn(|i| self.read_memory(self.index + i));
+                self.pattern = Some(pattern);
+            }
+            PITCHVx(x) => self.pitch = self.v[x],
             Unknown => {}
-        };
+        }
+        Ok(())
     }
 }
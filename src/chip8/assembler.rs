@@ -0,0 +1,315 @@
+use std::{collections::HashMap, error::Error, fmt};
+
+/// Address the assembled program is loaded at
+const ORIGIN: usize = 0x200;
+
+/// An error on a line of the source, counting from 1.
+#[derive(Debug)]
+pub struct AssembleError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AssembleError {}
+
+/// Assembles source in the syntax printed by the disassembler (Cowgod's
+/// mnemonics) into a ROM loaded at 0x200.
+///
+/// Besides instructions, a line may hold a `label:` (usable wherever an
+/// address or byte is expected), `db` or `dw` followed by comma-separated
+/// bytes or words, and a `;` comment. Numbers are decimal, or hex with a
+/// `0x`, `#` or `$` prefix, or binary with `0b`. XO-CHIP's `LD I, LONG`
+/// takes its address from a `dw` on the next line.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    let statements = parse(source)?;
+
+    // First pass: lay out the program to find where the labels point
+    let mut labels = HashMap::new();
+    let mut address = ORIGIN;
+    for (line, statement) in &statements {
+        if let Some(label) = &statement.label {
+            if labels.insert(label.to_ascii_lowercase(), address).is_some() {
+                return Err(AssembleError {
+                    line: *line,
+                    message: format!("label `{}` is defined twice", label),
+                });
+            }
+        }
+        address += statement.size();
+    }
+
+    // Second pass: encode
+    let mut rom = Vec::with_capacity(address - ORIGIN);
+    for (line, statement) in &statements {
+        statement
+            .encode(&labels, &mut rom)
+            .map_err(|message| AssembleError {
+                line: *line,
+                message,
+            })?;
+    }
+    Ok(rom)
+}
+
+struct Statement<'a> {
+    label: Option<&'a str>,
+    mnemonic: Option<String>,
+    operands: Vec<&'a str>,
+}
+
+fn parse(source: &str) -> Result<Vec<(usize, Statement<'_>)>, AssembleError> {
+    let mut statements = vec![];
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let mut text = text.split(';').next().unwrap_or_default().trim();
+
+        let mut label = None;
+        if let Some((name, rest)) = text.split_once(':') {
+            let name = name.trim();
+            if !is_identifier(name) {
+                return Err(AssembleError {
+                    line,
+                    message: format!("invalid label `{}`", name),
+                });
+            }
+            if is_reserved(name) {
+                return Err(AssembleError {
+                    line,
+                    message: format!("`{}` is reserved and can't be a label", name),
+                });
+            }
+            label = Some(name);
+            text = rest.trim();
+        }
+
+        let (mnemonic, operands) = match text.split_once(char::is_whitespace) {
+            Some((mnemonic, operands)) => (mnemonic, operands.split(',').map(str::trim).collect()),
+            None => (text, vec![]),
+        };
+        let mnemonic = (!mnemonic.is_empty()).then(|| mnemonic.to_ascii_uppercase());
+        if label.is_some() || mnemonic.is_some() {
+            statements.push((
+                line,
+                Statement {
+                    label,
+                    mnemonic,
+                    operands,
+                },
+            ));
+        }
+    }
+    Ok(statements)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    V(u16),
+    I,
+    IndirectI,
+    DT,
+    ST,
+    K,
+    F,
+    B,
+    HF,
+    R,
+    Long,
+    Pitch,
+    Value(usize),
+}
+
+impl Statement<'_> {
+    fn size(&self) -> usize {
+        match self.mnemonic.as_deref() {
+            None => 0,
+            Some("DB") => self.operands.len(),
+            Some("DW") => self.operands.len() * 2,
+            Some(_) => 2,
+        }
+    }
+
+    fn encode(&self, labels: &HashMap<String, usize>, rom: &mut Vec<u8>) -> Result<(), String> {
+        use Operand::*;
+
+        let Some(mnemonic) = self.mnemonic.as_deref() else {
+            return Ok(());
+        };
+        let operands = self
+            .operands
+            .iter()
+            .map(|operand| parse_operand(operand, labels))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if mnemonic == "DB" || mnemonic == "DW" {
+            for operand in operands {
+                let Value(value) = operand else {
+                    return Err(format!("`{}` expects numbers", mnemonic.to_lowercase()));
+                };
+                if mnemonic == "DB" {
+                    rom.push(byte(value)? as u8);
+                } else {
+                    rom.extend_from_slice(&word(value)?.to_be_bytes());
+                }
+            }
+            return Ok(());
+        }
+
+        let opcode = match (mnemonic, operands.as_slice()) {
+            ("CLS", []) => 0x00E0,
+            ("RET", []) => 0x00EE,
+            ("SYS", [Value(nnn)]) => address(*nnn)?,
+            ("JP", [Value(nnn)]) => 0x1000 | address(*nnn)?,
+            ("JP", [V(0), Value(nnn)]) => 0xB000 | address(*nnn)?,
+            ("CALL", [Value(nnn)]) => 0x2000 | address(*nnn)?,
+            ("SE", [V(x), Value(kk)]) => 0x3000 | x << 8 | byte(*kk)?,
+            ("SNE", [V(x), Value(kk)]) => 0x4000 | x << 8 | byte(*kk)?,
+            ("SE", [V(x), V(y)]) => 0x5000 | x << 8 | y << 4,
+            ("LD", [V(x), Value(kk)]) => 0x6000 | x << 8 | byte(*kk)?,
+            ("ADD", [V(x), Value(kk)]) => 0x7000 | x << 8 | byte(*kk)?,
+            ("LD", [V(x), V(y)]) => 0x8000 | x << 8 | y << 4,
+            ("OR", [V(x), V(y)]) => 0x8001 | x << 8 | y << 4,
+            ("AND", [V(x), V(y)]) => 0x8002 | x << 8 | y << 4,
+            ("XOR", [V(x), V(y)]) => 0x8003 | x << 8 | y << 4,
+            ("ADD", [V(x), V(y)]) => 0x8004 | x << 8 | y << 4,
+            ("SUB", [V(x), V(y)]) => 0x8005 | x << 8 | y << 4,
+            ("SHR", [V(x)]) => 0x8006 | x << 8,
+            ("SHR", [V(x), V(y)]) => 0x8006 | x << 8 | y << 4,
+            ("SUBN", [V(x), V(y)]) => 0x8007 | x << 8 | y << 4,
+            ("SHL", [V(x)]) => 0x800E | x << 8,
+            ("SHL", [V(x), V(y)]) => 0x800E | x << 8 | y << 4,
+            ("SNE", [V(x), V(y)]) => 0x9000 | x << 8 | y << 4,
+            ("LD", [I, Value(nnn)]) => 0xA000 | address(*nnn)?,
+            ("RND", [V(x), Value(kk)]) => 0xC000 | x << 8 | byte(*kk)?,
+            ("DRW", [V(x), V(y), Value(n)]) => 0xD000 | x << 8 | y << 4 | nibble(*n)?,
+            ("SKP", [V(x)]) => 0xE09E | x << 8,
+            ("SKNP", [V(x)]) => 0xE0A1 | x << 8,
+            ("LD", [V(x), DT]) => 0xF007 | x << 8,
+            ("LD", [V(x), K]) => 0xF00A | x << 8,
+            ("LD", [DT, V(x)]) => 0xF015 | x << 8,
+            ("LD", [ST, V(x)]) => 0xF018 | x << 8,
+            ("ADD", [I, V(x)]) => 0xF01E | x << 8,
+            ("LD", [F, V(x)]) => 0xF029 | x << 8,
+            ("LD", [B, V(x)]) => 0xF033 | x << 8,
+            ("LD", [IndirectI, V(x)]) => 0xF055 | x << 8,
+            ("LD", [V(x), IndirectI]) => 0xF065 | x << 8,
+            ("PLANE", [Value(n)]) => 0xF001 | nibble(*n)? << 8,
+            ("SCD", [Value(n)]) => 0x00C0 | nibble(*n)?,
+            ("SCU", [Value(n)]) => 0x00D0 | nibble(*n)?,
+            ("SCR", []) => 0x00FB,
+            ("SCL", []) => 0x00FC,
+            ("EXIT", []) => 0x00FD,
+            ("LOW", []) => 0x00FE,
+            ("HIGH", []) => 0x00FF,
+            ("LD", [HF, V(x)]) => 0xF030 | x << 8,
+            ("LD", [R, V(x)]) => 0xF075 | x << 8,
+            ("LD", [V(x), R]) => 0xF085 | x << 8,
+            ("SAVE", [V(x), V(y)]) => 0x5002 | x << 8 | y << 4,
+            ("LOAD", [V(x), V(y)]) => 0x5003 | x << 8 | y << 4,
+            ("LD", [I, Long]) => 0xF000,
+            ("AUDIO", []) => 0xF002,
+            ("LD", [Pitch, V(x)]) => 0xF03A | x << 8,
+            _ => {
+                return Err(format!(
+                    "invalid instruction `{} {}`",
+                    mnemonic,
+                    self.operands.join(", ")
+                ))
+            }
+        };
+        rom.extend_from_slice(&opcode.to_be_bytes());
+        Ok(())
+    }
+}
+
+fn parse_operand(text: &str, labels: &HashMap<String, usize>) -> Result<Operand, String> {
+    let upper = text.to_ascii_uppercase();
+    let operand = match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DT,
+        "ST" => Operand::ST,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "B" => Operand::B,
+        "HF" => Operand::HF,
+        "R" => Operand::R,
+        "LONG" => Operand::Long,
+        "PITCH" => Operand::Pitch,
+        _ => {
+            if let Some(register) = upper.strip_prefix('V').filter(|hex| hex.len() == 1) {
+                if let Ok(x) = u16::from_str_radix(register, 16) {
+                    return Ok(Operand::V(x));
+                }
+            }
+            if let Some(&address) = labels.get(&text.to_ascii_lowercase()) {
+                return Ok(Operand::Value(address));
+            }
+            Operand::Value(parse_number(text).ok_or_else(|| format!("unknown operand `{}`", text))?)
+        }
+    };
+    Ok(operand)
+}
+
+/// Parses a number in assembler syntax: decimal, `0x`/`#`/`$` hex or `0b` binary.
+pub fn parse_number(text: &str) -> Option<usize> {
+    let lower = text.to_ascii_lowercase();
+    let (digits, radix) = if let Some(hex) = lower
+        .strip_prefix("0x")
+        .or_else(|| lower.strip_prefix('#'))
+        .or_else(|| lower.strip_prefix('$'))
+    {
+        (hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        (binary, 2)
+    } else {
+        (lower.as_str(), 10)
+    };
+    usize::from_str_radix(digits, radix).ok()
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether `name` reads as a register or keyword operand, which would
+/// shadow a label of that name.
+fn is_reserved(name: &str) -> bool {
+    !matches!(
+        parse_operand(name, &HashMap::new()),
+        Ok(Operand::Value(_)) | Err(_)
+    )
+}
+
+fn address(value: usize) -> Result<u16, String> {
+    in_range(value, 0xFFF, "address")
+}
+
+fn byte(value: usize) -> Result<u16, String> {
+    in_range(value, 0xFF, "byte")
+}
+
+fn nibble(value: usize) -> Result<u16, String> {
+    in_range(value, 0xF, "nibble")
+}
+
+fn word(value: usize) -> Result<u16, String> {
+    in_range(value, 0xFFFF, "word")
+}
+
+fn in_range(value: usize, max: usize, kind: &str) -> Result<u16, String> {
+    if value <= max {
+        Ok(value as u16)
+    } else {
+        Err(format!("{} 0x{:X} is out of range", kind, value))
+    }
+}
@@ -1,75 +1,73 @@
-use cpal::{
-    traits::{DeviceTrait, HostTrait},
-    BuildStreamError, Stream,
-};
-use std::{
-    f32::consts::PI,
-    sync::{Arc, Mutex},
+mod device;
+mod file;
+mod null;
+mod tone;
+
+#[cfg(test)]
+pub(super) use self::device::queue_frame;
+pub use self::{
+    device::DeviceBackend,
+    file::FileBackend,
+    null::NullBackend,
+    tone::{Beeper, Tone, Waveform},
 };
+use super::sound::{AudioPattern, SoundFrame};
+use std::{path::Path, str::FromStr};
+
+/// Somewhere for the beeper to go.
+pub trait AudioBackend {
+    /// Called once per emulated frame with when the buzzer was on.
+    fn push_frame(&mut self, sound: &SoundFrame);
 
-const VOLUME: f32 = 0.05;
-const WAVE_FREQUENCY: f32 = 440.0;
+    /// From 0 (silent) to 1; changes are ramped like the buzzer itself.
+    fn set_volume(&mut self, volume: f32);
 
-pub struct Audio {
-    _stream: cpal::Stream,
-    active: Arc<Mutex<bool>>,
+    /// Flushes any buffered output; called before exiting.
+    fn finish(&mut self) {}
 }
 
-impl Audio {
-    pub fn new() -> Self {
-        // Initialize audio config
-        let host = cpal::default_host();
-        let device = host
-            .default_output_device()
-            .expect("[Audio] Failed to get default output device");
-        let config = device
-            .default_output_config()
-            .expect("[Audio] Failed to get default output config");
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AudioOutput {
+    /// The default output device, or no sound if there isn't one
+    #[default]
+    Device,
+    /// No sound at all
+    None,
+    /// A WAV file
+    File,
+}
 
-        let active = Arc::new(Mutex::new(true));
+impl FromStr for AudioOutput {
+    type Err = String;
 
-        // Start audio stream
-        let _stream = match config.sample_format() {
-            cpal::SampleFormat::F32 => {
-                Self::create_stream::<f32>(&device, &config.into(), Arc::clone(&active))
-            }
-            _ => panic!("[Audio] Unsupported sample format"),
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "device" => Ok(AudioOutput::Device),
+            "none" => Ok(AudioOutput::None),
+            "file" => Ok(AudioOutput::File),
+            _ => Err(format!(
+                "unknown audio output `{}` (expected device, none or file)",
+                name
+            )),
         }
-        .expect("[Audio] Failed to build output audio stream");
-
-        Self { _stream, active }
-    }
-
-    pub fn set_active(&self, active: bool) {
-        let mut active_lock = self.active.lock().unwrap();
-        *active_lock = active;
     }
+}
 
-    fn create_stream<T>(
-        device: &cpal::Device,
-        config: &cpal::StreamConfig,
-        active: Arc<Mutex<bool>>,
-    ) -> Result<Stream, BuildStreamError> {
-        let sample_rate = config.sample_rate.0 as f32;
-        let mut phase = 0.0;
+/// Opens the requested backend, falling back to silence (with a warning)
+/// when it isn't available.
+pub fn open_backend(output: AudioOutput, file: &Path, beeper: Beeper) -> Box<dyn AudioBackend> {
+    let backend: Result<Box<dyn AudioBackend>, String> = match output {
+        AudioOutput::Device => DeviceBackend::new(beeper)
+            .map(|backend| Box::new(backend) as Box<dyn AudioBackend>)
+            .map_err(|err| err.to_string()),
+        AudioOutput::None => Ok(Box::new(NullBackend)),
+        AudioOutput::File => FileBackend::new(file, beeper)
+            .map(|backend| Box::new(backend) as Box<dyn AudioBackend>)
+            .map_err(|err| format!("{}: {}", file.display(), err)),
+    };
 
-        device.build_output_stream(
-            config,
-            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                // Generate a sinusoidal wave of maximum amplitude
-                let active = *active.lock().unwrap();
-                let omega = 2.0 * PI * WAVE_FREQUENCY / sample_rate;
-                for sample in data.iter_mut() {
-                    *sample = if active {
-                        VOLUME * (omega * phase).sin()
-                    } else {
-                        0.0
-                    };
-                    phase = (phase + 0.5) % sample_rate;
-                }
-            },
-            |err| eprintln!("[Audio] Error occurred on output audio stream: {}", err),
-            None,
-        )
-    }
+    backend.unwrap_or_else(|err| {
+        eprintln!("[Audio] Continuing without sound: {}", err);
+        Box::new(NullBackend)
+    })
 }
@@ -0,0 +1,131 @@
+use super::{AudioBackend, Beeper, SoundFrame, Tone};
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    BuildStreamError, FromSample, SizedSample, Stream,
+};
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::error::Error;
+
+/// Frames of samples the ring buffer holds; more than this and whole new
+/// frames are dropped rather than adding latency
+const BUFFERED_FRAMES: usize = 6;
+/// Frames of silence queued up front, so jitter in the frame rate doesn't
+/// starve the device
+const PREFILLED_FRAMES: usize = 2;
+
+/// Plays the beeper on the default output device through cpal.
+///
+/// Samples are generated on the emulator side, one frame at a time from the
+/// buzzer's state during that frame, and handed to the audio thread through
+/// a lock-free ring buffer.
+pub struct DeviceBackend {
+    _stream: Stream,
+    producer: Producer<f32>,
+    tone: Tone,
+    sample_rate: f64,
+    /// Fractional samples carried over to the next frame, for sample rates
+    /// that aren't a multiple of 60 Hz
+    remainder: f64,
+    samples: Vec<f32>,
+}
+
+impl DeviceBackend {
+    pub fn new(beeper: Beeper) -> Result<Self, Box<dyn Error>> {
+        // Initialize audio config
+        let host = cpal::default_host();
+        let device = host
+            .default_output_device()
+            .ok_or("no default output device")?;
+        let config = device.default_output_config()?;
+
+        let stream_config = config.config();
+        let sample_rate = stream_config.sample_rate.0;
+        let frame_samples = (sample_rate / 60) as usize + 1;
+        let (mut producer, consumer) = RingBuffer::new(frame_samples * BUFFERED_FRAMES);
+        for _ in 0..frame_samples * PREFILLED_FRAMES {
+            let _ = producer.push(0.0);
+        }
+
+        // Start audio stream
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::I8 => create_stream::<i8>(&device, &stream_config, consumer),
+            cpal::SampleFormat::I16 => create_stream::<i16>(&device, &stream_config, consumer),
+            cpal::SampleFormat::I32 => create_stream::<i32>(&device, &stream_config, consumer),
+            cpal::SampleFormat::I64 => create_stream::<i64>(&device, &stream_config, consumer),
+            cpal::SampleFormat::U8 => create_stream::<u8>(&device, &stream_config, consumer),
+            cpal::SampleFormat::U16 => create_stream::<u16>(&device, &stream_config, consumer),
+            cpal::SampleFormat::U32 => create_stream::<u32>(&device, &stream_config, consumer),
+            cpal::SampleFormat::U64 => create_stream::<u64>(&device, &stream_config, consumer),
+            cpal::SampleFormat::F32 => create_stream::<f32>(&device, &stream_config, consumer),
+            cpal::SampleFormat::F64 => create_stream::<f64>(&device, &stream_config, consumer),
+            format => return Err(format!("unsupported sample format {}", format).into()),
+        }?;
+        stream.play()?;
+
+        Ok(Self {
+            _stream: stream,
+            producer,
+            tone: Tone::new(sample_rate, beeper),
+            sample_rate: sample_rate as f64,
+            remainder: 0.0,
+            samples: Vec::with_capacity(frame_samples),
+        })
+    }
+}
+
+impl AudioBackend for DeviceBackend {
+    fn push_frame(&mut self, sound: &SoundFrame) {
+        let samples = self.sample_rate / 60.0 + self.remainder;
+        let count = samples as usize;
+        self.remainder = samples - count as f64;
+        self.samples.resize(count, 0.0);
+        queue_frame(&mut self.producer, &mut self.tone, sound, &mut self.samples);
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        self.tone.set_volume(volume);
+    }
+}
+
+/// Generates a frame of `sound` the length of `samples` into it and queues
+/// it on `producer`.
+pub(in crate::chip8) fn queue_frame(
+    producer: &mut Producer<f32>,
+    tone: &mut Tone,
+    sound: &SoundFrame,
+    samples: &mut [f32],
+) {
+    // Drop the whole frame when the buffer is full, before generating it,
+    // so the tone carries on from the last frame queued instead of cutting
+    // off part way through one
+    let Ok(chunk) = producer.write_chunk_uninit(samples.len()) else {
+        return;
+    };
+    tone.write_frame(sound, samples);
+    chunk.fill_from_iter(samples.iter().copied());
+}
+
+fn create_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut consumer: Consumer<f32>,
+) -> Result<Stream, BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            // Interleaved: the same sample goes to every channel. Silence
+            // if the emulator falls behind (e.g. while paused)
+            for frame in data.chunks_mut(channels) {
+                let value = T::from_sample(consumer.pop().unwrap_or(0.0));
+                frame.fill(value);
+            }
+        },
+        |err| eprintln!("[Audio] Error occurred on output audio stream: {}", err),
+        None,
+    )
+}
@@ -0,0 +1,77 @@
+use super::{AudioBackend, Beeper, SoundFrame, Tone};
+use std::{
+    fs::File,
+    io::{BufWriter, Error},
+    path::Path,
+};
+
+pub const SAMPLE_RATE: u32 = 44100;
+/// 44100 / 60 is exact, so the audio never drifts from emulated time
+const SAMPLES_PER_FRAME: u32 = SAMPLE_RATE / 60;
+
+/// Writes the beeper to a 16-bit mono WAV file, generating one frame of
+/// samples per emulated frame.
+pub struct FileBackend {
+    writer: Option<hound::WavWriter<BufWriter<File>>>,
+    tone: Tone,
+    samples: Vec<f32>,
+}
+
+impl FileBackend {
+    pub fn new(path: &Path, beeper: Beeper) -> Result<Self, Error> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let writer = hound::WavWriter::create(path, spec).map_err(Error::other)?;
+
+        Ok(Self {
+            writer: Some(writer),
+            tone: Tone::new(SAMPLE_RATE, beeper),
+            samples: vec![0.0; SAMPLES_PER_FRAME as usize],
+        })
+    }
+
+    /// Appends one 60 Hz frame of samples.
+    pub fn write_frame(&mut self, sound: &SoundFrame) -> Result<(), Error> {
+        let Some(writer) = &mut self.writer else {
+            return Ok(());
+        };
+        self.tone.write_frame(sound, &mut self.samples);
+        for &sample in &self.samples {
+            writer
+                .write_sample((sample * i16::MAX as f32) as i16)
+                .map_err(Error::other)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the WAV header sizes; no more frames are written after this.
+    pub fn close(&mut self) -> Result<(), Error> {
+        match self.writer.take() {
+            Some(writer) => writer.finalize().map_err(Error::other),
+            None => Ok(()),
+        }
+    }
+}
+
+impl AudioBackend for FileBackend {
+    fn push_frame(&mut self, sound: &SoundFrame) {
+        if let Err(err) = self.write_frame(sound) {
+            eprintln!("[Audio] Failed to write audio file: {}", err);
+            self.writer = None;
+        }
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        self.tone.set_volume(volume);
+    }
+
+    fn finish(&mut self) {
+        if let Err(err) = self.close() {
+            eprintln!("[Audio] Failed to save audio file: {}", err);
+        }
+    }
+}
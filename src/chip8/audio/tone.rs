@@ -0,0 +1,147 @@
+use super::{AudioPattern, SoundFrame};
+use std::{f32::consts::PI, str::FromStr};
+
+/// Amplitude of the beeper at full volume
+const MAX_AMPLITUDE: f32 = 0.2;
+/// How long the beeper takes to fade in or out, so toggling it doesn't click
+const RAMP_SECONDS: f32 = 0.005;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Waveform {
+    #[default]
+    Sine,
+    Square,
+    Triangle,
+    /// Random levels held for one cycle each
+    Noise,
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "sine" => Ok(Waveform::Sine),
+            "square" => Ok(Waveform::Square),
+            "triangle" => Ok(Waveform::Triangle),
+            "noise" => Ok(Waveform::Noise),
+            _ => Err(format!(
+                "unknown waveform `{}` (expected sine, square, triangle or noise)",
+                name
+            )),
+        }
+    }
+}
+
+/// What the buzzer sounds like.
+#[derive(Clone, Copy, Debug)]
+pub struct Beeper {
+    pub waveform: Waveform,
+    /// Pitch in Hz
+    pub frequency: f32,
+    /// From 0 (silent) to 1
+    pub volume: f32,
+}
+
+impl Default for Beeper {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Sine,
+            frequency: 440.0,
+            volume: 0.25,
+        }
+    }
+}
+
+/// Beeper waveform generated sample by sample, so the same output can be
+/// reproduced from emulated time (e.g. for recordings).
+pub struct Tone {
+    beeper: Beeper,
+    sample_rate: f32,
+    /// Position within the current wave cycle, from 0 to 1
+    phase: f32,
+    /// Current amplitude, ramping towards the volume or silence
+    gain: f32,
+    /// 15-bit LFSR for the noise waveform
+    lfsr: u16,
+    noise: f32,
+    /// XO-CHIP pattern played instead of the waveform, with the phase
+    /// running through the whole loop
+    pattern: Option<AudioPattern>,
+}
+
+impl Tone {
+    pub fn new(sample_rate: u32, beeper: Beeper) -> Self {
+        Self {
+            beeper,
+            sample_rate: sample_rate as f32,
+            phase: 0.0,
+            gain: 0.0,
+            lfsr: 1,
+            noise: 1.0,
+            pattern: None,
+        }
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.beeper.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn next_sample(&mut self, active: bool) -> f32 {
+        let target = if active {
+            self.beeper.volume * MAX_AMPLITUDE
+        } else {
+            0.0
+        };
+        let step = MAX_AMPLITUDE / (RAMP_SECONDS * self.sample_rate);
+        self.gain = if self.gain < target {
+            (self.gain + step).min(target)
+        } else {
+            (self.gain - step).max(target)
+        };
+
+        let sample = self.gain * self.wave();
+        let frequency =
+            (self.pattern).map_or(self.beeper.frequency, |pattern| pattern.loops_per_second());
+        let phase = self.phase + frequency / self.sample_rate;
+        if phase >= 1.0 {
+            self.next_noise();
+        }
+        self.phase = phase.rem_euclid(1.0);
+        sample
+    }
+
+    /// Fills `samples` with one frame of sound, spread evenly over the frame.
+    pub fn write_frame(&mut self, sound: &SoundFrame, samples: &mut [f32]) {
+        self.pattern = sound.pattern();
+        let count = samples.len() as f32;
+        for (i, sample) in samples.iter_mut().enumerate() {
+            *sample = self.next_sample(sound.active_at(i as f32 / count));
+        }
+    }
+
+    /// The waveform at the current phase, from -1 to 1.
+    fn wave(&self) -> f32 {
+        if let Some(pattern) = self.pattern {
+            return pattern.sample(self.phase);
+        }
+        match self.beeper.waveform {
+            Waveform::Sine => (2.0 * PI * self.phase).sin(),
+            Waveform::Square => {
+                if self.phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            Waveform::Noise => self.noise,
+        }
+    }
+
+    fn next_noise(&mut self) {
+        let bit = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+        self.lfsr = (self.lfsr >> 1) | (bit << 14);
+        self.noise = if self.lfsr & 1 == 1 { 1.0 } else { -1.0 };
+    }
+}
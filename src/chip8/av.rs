@@ -0,0 +1,83 @@
+use super::{
+    audio::{Beeper, FileBackend},
+    capture::{scale, CAPTURE_HEIGHT, CAPTURE_WIDTH},
+    sound::SoundFrame,
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Error, Write},
+    path::{Path, PathBuf},
+};
+
+const FPS: u32 = 60;
+
+/// Records the beeper to `<base>.wav` and the display to `<base>.y4m`, one
+/// emulated frame at a time so both streams stay in sync. Encode them with
+/// e.g. `ffmpeg -i base.y4m -i base.wav clip.mp4`.
+pub struct AvRecorder {
+    base: PathBuf,
+    wav: FileBackend,
+    y4m: BufWriter<File>,
+}
+
+impl AvRecorder {
+    pub fn new(base: &Path, beeper: Beeper) -> Result<Self, Error> {
+        let wav = FileBackend::new(&with_extension(base, "wav"), beeper)?;
+
+        let mut y4m = BufWriter::new(File::create(with_extension(base, "y4m"))?);
+        writeln!(
+            y4m,
+            "YUV4MPEG2 W{CAPTURE_WIDTH} H{CAPTURE_HEIGHT} F{FPS}:1 Ip A1:1 C444"
+        )?;
+
+        Ok(Self {
+            base: base.to_path_buf(),
+            wav,
+            y4m,
+        })
+    }
+
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// Appends one frame: the RGBA screen and when the buzzer was on.
+    pub fn push(&mut self, frame: &[u8], sound: &SoundFrame) -> Result<(), Error> {
+        self.wav.write_frame(sound)?;
+
+        let rgba = scale(frame);
+        let pixels = rgba.len() / 4;
+        let mut planes = vec![0; pixels * 3];
+        for (i, pixel) in rgba.chunks_exact(4).enumerate() {
+            let (y, u, v) = rgb_to_yuv(pixel[0], pixel[1], pixel[2]);
+            planes[i] = y;
+            planes[pixels + i] = u;
+            planes[pixels * 2 + i] = v;
+        }
+        self.y4m.write_all(b"FRAME\n")?;
+        self.y4m.write_all(&planes)
+    }
+
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.y4m.flush()?;
+        self.wav.close()
+    }
+}
+
+/// `base` with `.extension` appended, keeping any dots already in it (e.g.
+/// `clip.v1` becomes `clip.v1.wav`).
+fn with_extension(base: &Path, extension: &str) -> PathBuf {
+    let mut path = base.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// BT.601 limited range conversion, as expected by most Y4M consumers.
+fn rgb_to_yuv(r: u8, g: u8, b: u8) -> (u8, u8, u8) {
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    let y = ((66 * r + 129 * g + 25 * b + 128) >> 8) + 16;
+    let u = ((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128;
+    let v = ((112 * r - 94 * g - 18 * b + 128) >> 8) + 128;
+    (y as u8, u as u8, v as u8)
+}
@@ -0,0 +1,248 @@
+use super::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Error, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Size of a hires screen pixel in screenshots and recordings, so a CHIP-8
+/// pixel is 8x8
+const CAPTURE_SCALE: usize = 4;
+pub const CAPTURE_WIDTH: usize = SCREEN_WIDTH * CAPTURE_SCALE;
+pub const CAPTURE_HEIGHT: usize = SCREEN_HEIGHT * CAPTURE_SCALE;
+const FPS: usize = 60;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RecordingFormat {
+    #[default]
+    Gif,
+    Apng,
+}
+
+impl RecordingFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordingFormat::Gif => "gif",
+            RecordingFormat::Apng => "png",
+        }
+    }
+}
+
+impl FromStr for RecordingFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "gif" => Ok(RecordingFormat::Gif),
+            "apng" | "png" => Ok(RecordingFormat::Apng),
+            _ => Err(format!("unknown format `{}` (expected gif or apng)", name)),
+        }
+    }
+}
+
+/// Saves an RGBA screen frame as a scaled PNG.
+pub fn save_png(path: &Path, frame: &[u8]) -> Result<(), Error> {
+    save_image(path, CAPTURE_WIDTH, CAPTURE_HEIGHT, &scale(frame))
+}
+
+/// Saves RGBA pixels of any size as a PNG.
+pub fn save_image(path: &Path, width: usize, height: usize, rgba: &[u8]) -> Result<(), Error> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut writer = png_encoder(file, width, height)
+        .write_header()
+        .map_err(Error::other)?;
+    writer.write_image_data(rgba).map_err(Error::other)?;
+    writer.finish().map_err(Error::other)
+}
+
+/// Whether a PNG saved by `save_png` shows the same image as the frame.
+pub fn matches_png(path: &Path, frame: &[u8]) -> Result<bool, Error> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(Error::other)?;
+    let mut image = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut image).map_err(Error::other)?;
+    if (info.width, info.height) != (CAPTURE_WIDTH as u32, CAPTURE_HEIGHT as u32)
+        || info.color_type != png::ColorType::Rgba
+    {
+        return Ok(false);
+    }
+    Ok(image[..info.buffer_size()] == scale(frame))
+}
+
+/// Encodes RGBA screen frames (one per 60 Hz frame) into an animation as
+/// they arrive, so a recording's length doesn't grow memory.
+pub struct Recorder {
+    path: PathBuf,
+    encoder: Encoder,
+    frames: usize,
+}
+
+enum Encoder {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Apng(png::Writer<BufWriter<File>>),
+}
+
+impl Recorder {
+    pub fn new(path: PathBuf, format: RecordingFormat) -> Result<Self, Error> {
+        let encoder = match format {
+            RecordingFormat::Gif => {
+                let file = BufWriter::new(File::create(&path)?);
+                let mut encoder =
+                    gif::Encoder::new(file, CAPTURE_WIDTH as u16, CAPTURE_HEIGHT as u16, &[])
+                        .map_err(Error::other)?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(Error::other)?;
+                Encoder::Gif(encoder)
+            }
+            RecordingFormat::Apng => {
+                Encoder::Apng(start_apng(BufWriter::new(File::create(&path)?))?)
+            }
+        };
+        Ok(Self {
+            path,
+            encoder,
+            frames: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn push(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let i = self.frames;
+        match &mut self.encoder {
+            Encoder::Gif(encoder) => {
+                let mut gif_frame = gif_frame(&scale(frame));
+                // GIF delays are in hundredths of a second, so alternate 2
+                // and 1 to average out at 60 fps
+                gif_frame.delay = ((i + 1) * 100 / FPS - i * 100 / FPS) as u16;
+                encoder.write_frame(&gif_frame).map_err(Error::other)?;
+            }
+            Encoder::Apng(writer) => {
+                writer
+                    .write_image_data(&scale(frame))
+                    .map_err(Error::other)?;
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Finishes the file.
+    pub fn save(self) -> Result<(), Error> {
+        match self.encoder {
+            Encoder::Gif(encoder) => encoder.into_inner()?.flush(),
+            Encoder::Apng(writer) => {
+                writer.finish().map_err(Error::other)?;
+                let mut file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+                patch_frame_count(&mut file, self.frames as u32)
+            }
+        }
+    }
+}
+
+/// Writes the header of an APNG of capture-sized frames to `out`, ready for
+/// the frames. The frame count isn't known until the end, so it has to be
+/// patched in with `patch_frame_count`.
+pub(super) fn start_apng<W: Write>(out: W) -> Result<png::Writer<W>, Error> {
+    let mut encoder = png_encoder(out, CAPTURE_WIDTH, CAPTURE_HEIGHT);
+    encoder.validate_sequence(false);
+    encoder.set_animated(u32::MAX, 0).map_err(Error::other)?;
+    encoder
+        .set_frame_delay(1, FPS as u16)
+        .map_err(Error::other)?;
+    encoder.write_header().map_err(Error::other)
+}
+
+/// Writes the number of frames into the `acTL` chunk of an APNG.
+pub(super) fn patch_frame_count<F: Read + Write + Seek>(
+    file: &mut F,
+    frames: u32,
+) -> Result<(), Error> {
+    // Chunks follow the 8 byte signature: length, type, data and CRC
+    let mut offset = 8;
+    loop {
+        let mut header = [0; 8];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        if &header[4..] == b"acTL" {
+            let mut data = [0; 8];
+            file.read_exact(&mut data)?;
+            data[..4].copy_from_slice(&frames.to_be_bytes());
+            let mut crc = crc32fast::Hasher::new();
+            crc.update(b"acTL");
+            crc.update(&data);
+            file.seek(SeekFrom::Start(offset + 8))?;
+            file.write_all(&data)?;
+            return file.write_all(&crc.finalize().to_be_bytes());
+        }
+        if &header[4..] == b"IDAT" {
+            return Err(Error::new(ErrorKind::InvalidData, "no acTL chunk"));
+        }
+        offset += 12 + length;
+    }
+}
+
+fn png_encoder<W: Write>(out: W, width: usize, height: usize) -> png::Encoder<'static, W> {
+    let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+}
+
+/// Indexed GIF frame, quantizing only if the frame has more than 256 colors.
+fn gif_frame(rgba: &[u8]) -> gif::Frame<'static> {
+    let mut palette = HashMap::new();
+    let mut indices = Vec::with_capacity(rgba.len() / 4);
+    for pixel in rgba.chunks_exact(4) {
+        let next = palette.len();
+        let index = *palette
+            .entry([pixel[0], pixel[1], pixel[2]])
+            .or_insert(next);
+        indices.push(index as u8);
+        if palette.len() > 256 {
+            let mut rgba = rgba.to_vec();
+            return gif::Frame::from_rgba_speed(
+                CAPTURE_WIDTH as u16,
+                CAPTURE_HEIGHT as u16,
+                &mut rgba,
+                10,
+            );
+        }
+    }
+
+    let mut colors = vec![0; palette.len() * 3];
+    for (rgb, index) in palette {
+        colors[index * 3..index * 3 + 3].copy_from_slice(&rgb);
+    }
+    gif::Frame::from_palette_pixels(
+        CAPTURE_WIDTH as u16,
+        CAPTURE_HEIGHT as u16,
+        indices,
+        colors,
+        None,
+    )
+}
+
+/// Nearest-neighbor upscale of an RGBA screen frame by `CAPTURE_SCALE`.
+pub fn scale(frame: &[u8]) -> Vec<u8> {
+    let mut scaled = Vec::with_capacity(CAPTURE_WIDTH * CAPTURE_HEIGHT * 4);
+    for row in frame.chunks_exact(SCREEN_WIDTH * 4) {
+        let mut scaled_row = Vec::with_capacity(CAPTURE_WIDTH * 4);
+        for pixel in row.chunks_exact(4) {
+            for _ in 0..CAPTURE_SCALE {
+                scaled_row.extend_from_slice(pixel);
+            }
+        }
+        for _ in 0..CAPTURE_SCALE {
+            scaled.extend_from_slice(&scaled_row);
+        }
+    }
+    scaled
+}
@@ -0,0 +1,120 @@
+use super::{palette::Palette, quirks::Platform, quirks::QuirkOverrides, Rgba};
+use serde::{Deserialize, Deserializer};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Error, ErrorKind},
+};
+use winit::event::VirtualKeyCode;
+
+const BUILTIN: &str = include_str!("database.toml");
+
+/// Settings for a single ROM, keyed by its SHA-1 in the database.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RomConfig {
+    pub title: Option<String>,
+    pub platform: Option<Platform>,
+    #[serde(default)]
+    pub quirks: QuirkOverrides,
+    /// Instructions executed per frame
+    pub speed: Option<u32>,
+    /// CHIP-8 key (hex digit) to keyboard key
+    #[serde(default)]
+    pub keys: HashMap<String, VirtualKeyCode>,
+    /// Preset palette, see `palette::PALETTES`
+    pub palette: Option<Palette>,
+    /// Colors overriding those of the palette
+    #[serde(default)]
+    pub colors: Colors,
+}
+
+impl RomConfig {
+    /// The palette `chosen` on the command line, which wins over the
+    /// database, or else the ROM's palette (or the default one) with its
+    /// color overrides applied.
+    pub fn choose_palette(&self, chosen: Option<Palette>) -> Palette {
+        if let Some(palette) = chosen {
+            return palette;
+        }
+        let mut palette = self.palette.unwrap_or_default();
+        if let Some(background) = self.colors.background {
+            palette.colors[0] = background;
+        }
+        if let Some(foreground) = self.colors.foreground {
+            palette.colors[1] = foreground;
+        }
+        palette
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Colors {
+    #[serde(default, deserialize_with = "deserialize_color")]
+    pub foreground: Option<Rgba>,
+    #[serde(default, deserialize_with = "deserialize_color")]
+    pub background: Option<Rgba>,
+}
+
+#[derive(Default)]
+pub struct Database {
+    entries: HashMap<String, RomConfig>,
+}
+
+impl Database {
+    /// The database shipped with the interpreter.
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN).expect("[DB] Built-in database is invalid")
+    }
+
+    /// The built-in database, with entries from a local file taking precedence.
+    pub fn with_overrides(filename: &str) -> Result<Self, Error> {
+        let mut database = Self::builtin();
+        let local = Self::parse(&fs::read_to_string(filename)?)?;
+        database.entries.extend(local.entries);
+        Ok(database)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let entries: HashMap<String, RomConfig> =
+            toml::from_str(source).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        let entries = entries
+            .into_iter()
+            .map(|(hash, config)| (hash.to_lowercase(), config))
+            .collect();
+        Ok(Self { entries })
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomConfig> {
+        self.entries.get(&rom_hash(rom))
+    }
+}
+
+/// Lowercase hex SHA-1 of the ROM contents.
+pub fn rom_hash(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+/// Parses `#RRGGBB` or `#RRGGBBAA`.
+pub fn parse_color(value: &str) -> Option<Rgba> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return None;
+    }
+    let mut rgba = [0xff; 4];
+    for (i, channel) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(rgba)
+}
+
+fn deserialize_color<'de, D>(deserializer: D) -> Result<Option<Rgba>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    parse_color(&value)
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid color `{value}`")))
+}
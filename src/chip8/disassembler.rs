@@ -0,0 +1,87 @@
+use super::{assembler::assemble, instruction::Instruction, quirks::Platform};
+use std::collections::HashMap;
+
+/// Address ROMs are loaded at
+const ORIGIN: usize = 0x200;
+
+/// One line of a ROM listing.
+pub struct Line {
+    pub address: usize,
+    pub bytes: Vec<u8>,
+    /// Assembler source for the bytes: an instruction, or `db`/`dw` for
+    /// anything that doesn't decode to one
+    pub text: String,
+}
+
+/// Decodes a ROM two bytes at a time, as if it were all code for
+/// `platform`. The listing assembles back to the same bytes.
+pub fn disassemble(rom: &[u8], platform: Platform) -> Vec<Line> {
+    rom.chunks(2)
+        .enumerate()
+        .map(|(i, bytes)| {
+            let text = match bytes {
+                [high, low] => {
+                    let opcode = u16::from_be_bytes([*high, *low]);
+                    let text = Instruction::from_opcode(opcode, platform).disassemble();
+                    // Unknown opcodes, and fields the instruction ignores
+                    // (e.g. Y in SHR), don't survive a round trip
+                    if assemble(&text).is_ok_and(|assembled| assembled == bytes) {
+                        text
+                    } else {
+                        format!("dw 0x{:04X}", opcode)
+                    }
+                }
+                _ => format!("db 0x{:02X}", bytes[0]),
+            };
+            Line {
+                address: ORIGIN + i * 2,
+                bytes: bytes.to_vec(),
+                text,
+            }
+        })
+        .collect()
+}
+
+/// How many times each kind of instruction for `platform` appears in the
+/// ROM, most common first.
+pub fn histogram(rom: &[u8], platform: Platform) -> Vec<(&'static str, usize)> {
+    let mut counts = HashMap::new();
+    for opcode in opcodes(rom) {
+        *counts
+            .entry(Instruction::from_opcode(opcode, platform).pattern())
+            .or_insert(0) += 1;
+    }
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    counts
+}
+
+/// Guesses the platform a ROM was written for from the extended opcodes it
+/// uses. Data can look like opcodes too, so this is only a hint.
+pub fn detect_platform(rom: &[u8]) -> Platform {
+    let mut platform = Platform::Chip8;
+    for opcode in opcodes(rom) {
+        let kk = opcode & 0xFF;
+        let xo_chip = opcode == 0xF000
+            || opcode == 0xF002
+            || opcode & 0xFFF0 == 0x00D0
+            || opcode & 0xF00E == 0x5002
+            || (opcode & 0xF000 == 0xF000 && (kk == 0x01 || kk == 0x3A));
+        let super_chip = matches!(opcode, 0x00FB..=0x00FF)
+            || opcode & 0xFFF0 == 0x00C0
+            || opcode & 0xF00F == 0xD000
+            || (opcode & 0xF000 == 0xF000 && matches!(kk, 0x30 | 0x75 | 0x85));
+        if xo_chip {
+            return Platform::XoChip;
+        }
+        if super_chip {
+            platform = Platform::SuperChip;
+        }
+    }
+    platform
+}
+
+fn opcodes(rom: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    rom.chunks_exact(2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
@@ -0,0 +1,42 @@
+use super::{
+    filter::{mix, DisplayFilter, Persistence},
+    palette::Palette,
+    Screen, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+
+/// Turns the CHIP-8 screen into RGBA pixels, shared by every frontend.
+pub struct Display {
+    pub palette: Palette,
+    pub filter: DisplayFilter,
+    persistence: Persistence,
+}
+
+impl Display {
+    pub fn new(palette: Palette, filter: DisplayFilter) -> Self {
+        Self {
+            palette,
+            filter,
+            persistence: Persistence::new(),
+        }
+    }
+
+    /// Records the screen at the end of a frame, for the filters.
+    pub fn push(&mut self, screen: &Screen) {
+        self.persistence.push(screen);
+    }
+
+    /// The screen as RGBA pixels, with the palette and filter applied.
+    pub fn frame_rgba(&self, screen: &Screen) -> Vec<u8> {
+        let mut frame = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 4];
+        for (i, frame_pixel) in frame.chunks_exact_mut(4).enumerate() {
+            let x = i % SCREEN_WIDTH;
+            let y = i / SCREEN_WIDTH;
+            let (pixel, level) = self.persistence.pixel(self.filter, screen, x, y);
+
+            let rgba = mix(self.palette.color(0), self.palette.color(pixel), level);
+
+            frame_pixel.copy_from_slice(&rgba);
+        }
+        frame
+    }
+}
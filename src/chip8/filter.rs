@@ -0,0 +1,117 @@
+use super::{Rgba, Screen, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::str::FromStr;
+
+/// How much of a pixel's brightness is left one frame after it turns off
+const PHOSPHOR_DECAY: f32 = 0.5;
+
+/// Anti-flicker filter applied when rendering the screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DisplayFilter {
+    /// Show the screen as is
+    #[default]
+    None,
+    /// Pixels fade out over a few frames after turning off
+    Phosphor,
+    /// Pixels lit in either of the last two frames are shown
+    Blend,
+}
+
+const FILTERS: [DisplayFilter; 3] = [
+    DisplayFilter::None,
+    DisplayFilter::Phosphor,
+    DisplayFilter::Blend,
+];
+
+impl DisplayFilter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DisplayFilter::None => "none",
+            DisplayFilter::Phosphor => "phosphor",
+            DisplayFilter::Blend => "blend",
+        }
+    }
+
+    /// The filter following this one, wrapping around.
+    pub fn next(&self) -> DisplayFilter {
+        let i = FILTERS
+            .iter()
+            .position(|filter| filter == self)
+            .unwrap_or(0);
+        FILTERS[(i + 1) % FILTERS.len()]
+    }
+}
+
+impl FromStr for DisplayFilter {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        FILTERS
+            .into_iter()
+            .find(|filter| filter.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!(
+                    "unknown filter `{}` (expected none, phosphor or blend)",
+                    name
+                )
+            })
+    }
+}
+
+/// Screen history needed by the filters, updated once per frame.
+pub struct Persistence {
+    intensity: [[f32; SCREEN_WIDTH]; SCREEN_HEIGHT],
+    /// Last lit value of each pixel, the color it fades out in
+    fading: Screen,
+    previous: Screen,
+    last: Screen,
+}
+
+impl Persistence {
+    pub fn new() -> Self {
+        Self {
+            intensity: [[0.0; SCREEN_WIDTH]; SCREEN_HEIGHT],
+            fading: [[0; SCREEN_WIDTH]; SCREEN_HEIGHT],
+            previous: [[0; SCREEN_WIDTH]; SCREEN_HEIGHT],
+            last: [[0; SCREEN_WIDTH]; SCREEN_HEIGHT],
+        }
+    }
+
+    /// Records the screen at the end of a frame.
+    pub fn push(&mut self, screen: &Screen) {
+        self.previous = std::mem::replace(&mut self.last, *screen);
+        for (y, screen_row) in screen.iter().enumerate() {
+            for (x, &pixel) in screen_row.iter().enumerate() {
+                let intensity = &mut self.intensity[y][x];
+                if pixel != 0 {
+                    *intensity = 1.0;
+                    self.fading[y][x] = pixel;
+                } else {
+                    *intensity *= PHOSPHOR_DECAY;
+                }
+            }
+        }
+    }
+
+    /// The value of a pixel to color it with, and its brightness between 0
+    /// (off) and 1 (fully lit).
+    pub fn pixel(&self, filter: DisplayFilter, screen: &Screen, x: usize, y: usize) -> (u8, f32) {
+        let pixel = screen[y][x];
+        if pixel != 0 {
+            return (pixel, 1.0);
+        }
+        match filter {
+            DisplayFilter::None => (0, 0.0),
+            DisplayFilter::Phosphor => (self.fading[y][x], self.intensity[y][x]),
+            DisplayFilter::Blend => (self.previous[y][x], 1.0),
+        }
+    }
+}
+
+/// Linear interpolation between two colors.
+pub fn mix(from: Rgba, to: Rgba, amount: f32) -> Rgba {
+    let mut rgba = from;
+    for (channel, (&from, &to)) in rgba.iter_mut().zip(from.iter().zip(&to)) {
+        *channel = (from as f32 + (to as f32 - from as f32) * amount).round() as u8;
+    }
+    rgba
+}
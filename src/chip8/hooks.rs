@@ -0,0 +1,107 @@
+use super::{Field, MachineState};
+use std::{cell::RefCell, rc::Rc};
+
+/// Callbacks the interpreter fires as it runs, for coverage tools,
+/// achievements and other observers. Every method does nothing by default.
+pub trait Hook {
+    /// Before the instruction at `state.pc` runs.
+    fn before_instruction(&mut self, _state: &MachineState, _opcode: u16) {}
+
+    /// After the instruction at `address` ran.
+    fn after_instruction(&mut self, _state: &MachineState, _address: usize, _opcode: u16) {}
+
+    /// A byte of memory an instruction read. Fetching instructions doesn't
+    /// count.
+    fn memory_read(&mut self, _address: usize, _value: u8) {}
+
+    /// A byte of memory an instruction wrote.
+    fn memory_write(&mut self, _address: usize, _value: u8) {}
+
+    /// A sprite `n` rows high drawn at (`x`, `y`) by `DXYN`, and whether a
+    /// lit pixel was turned off.
+    fn sprite_drawn(&mut self, _x: u8, _y: u8, _n: u8, _collision: bool) {}
+
+    /// The screen cleared by `CLS`.
+    fn screen_cleared(&mut self) {}
+
+    /// The buzzer starting or stopping.
+    fn sound_changed(&mut self, _active: bool) {}
+
+    /// Registers, timers or bytes of memory the hook wants overwritten,
+    /// taken after each event that passes the machine state, so a hook can
+    /// change the machine before the next instruction is fetched.
+    fn take_writes(&mut self) -> Vec<(Field, usize)> {
+        vec![]
+    }
+}
+
+/// Lets callers keep a handle on a hook they added, to read what it
+/// collected.
+impl<H: Hook> Hook for Rc<RefCell<H>> {
+    fn before_instruction(&mut self, state: &MachineState, opcode: u16) {
+        self.borrow_mut().before_instruction(state, opcode);
+    }
+
+    fn after_instruction(&mut self, state: &MachineState, address: usize, opcode: u16) {
+        self.borrow_mut().after_instruction(state, address, opcode);
+    }
+
+    fn memory_read(&mut self, address: usize, value: u8) {
+        self.borrow_mut().memory_read(address, value);
+    }
+
+    fn memory_write(&mut self, address: usize, value: u8) {
+        self.borrow_mut().memory_write(address, value);
+    }
+
+    fn sprite_drawn(&mut self, x: u8, y: u8, n: u8, collision: bool) {
+        self.borrow_mut().sprite_drawn(x, y, n, collision);
+    }
+
+    fn screen_cleared(&mut self) {
+        self.borrow_mut().screen_cleared();
+    }
+
+    fn sound_changed(&mut self, active: bool) {
+        self.borrow_mut().sound_changed(active);
+    }
+
+    fn take_writes(&mut self) -> Vec<(Field, usize)> {
+        self.borrow_mut().take_writes()
+    }
+}
+
+/// Identifies a hook added to the interpreter, to remove it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HookId(usize);
+
+/// The hooks added to the interpreter, fired in the order they were added.
+#[derive(Default)]
+pub(super) struct Hooks {
+    next_id: usize,
+    hooks: Vec<(HookId, Box<dyn Hook>)>,
+}
+
+impl Hooks {
+    pub fn add(&mut self, hook: Box<dyn Hook>) -> HookId {
+        let id = HookId(self.next_id);
+        self.next_id += 1;
+        self.hooks.push((id, hook));
+        id
+    }
+
+    pub fn remove(&mut self, id: HookId) -> Option<Box<dyn Hook>> {
+        let position = self.hooks.iter().position(|(hook_id, _)| *hook_id == id)?;
+        Some(self.hooks.remove(position).1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    pub fn fire(&mut self, mut call: impl FnMut(&mut dyn Hook)) {
+        for (_, hook) in &mut self.hooks {
+            call(hook.as_mut());
+        }
+    }
+}
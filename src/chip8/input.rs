@@ -1,50 +1,82 @@
+use std::collections::HashMap;
 use winit::event::VirtualKeyCode;
 use winit_input_helper::WinitInputHelper;
 
 pub type Keys = [bool; 16];
-type Pause = bool;
+/// Keyboard key bound to each CHIP-8 key, indexed by the CHIP-8 key
+pub type KeyMap = [VirtualKeyCode; 16];
 
-pub fn get_processed_input(input: &WinitInputHelper) -> (Keys, Pause) {
-    let key_1 = input.key_held(VirtualKeyCode::Key1);
-    let key_2 = input.key_held(VirtualKeyCode::Key2);
-    let key_3 = input.key_held(VirtualKeyCode::Key3);
-    let key_c = input.key_held(VirtualKeyCode::Key4);
-
-    let key_4 = input.key_held(VirtualKeyCode::Q);
-    let key_5 = input.key_held(VirtualKeyCode::W);
-    let key_6 = input.key_held(VirtualKeyCode::E);
-    let key_d = input.key_held(VirtualKeyCode::R);
-
-    let key_7 = input.key_held(VirtualKeyCode::A);
-    let key_8 = input.key_held(VirtualKeyCode::S);
-    let key_9 = input.key_held(VirtualKeyCode::D);
-    let key_e = input.key_held(VirtualKeyCode::F);
+/// Emulator controls pressed this frame
+pub struct Hotkeys {
+    pub toggle_pause: bool,
+    pub cycle_palette: bool,
+    pub cycle_filter: bool,
+    pub screenshot: bool,
+    pub toggle_recording: bool,
+    pub toggle_mute: bool,
+    pub volume_down: bool,
+    pub volume_up: bool,
+    pub toggle_browser: bool,
+    pub reset: bool,
+    pub hard_reset: bool,
+    pub slow_down: bool,
+    pub speed_up: bool,
+    pub normal_speed: bool,
+    pub advance_frame: bool,
+}
 
-    let key_a = input.key_held(VirtualKeyCode::Z);
-    let key_0 = input.key_held(VirtualKeyCode::X);
-    let key_b = input.key_held(VirtualKeyCode::C);
-    let key_f = input.key_held(VirtualKeyCode::V);
+pub const DEFAULT_KEYMAP: KeyMap = [
+    VirtualKeyCode::X,    // 0
+    VirtualKeyCode::Key1, // 1
+    VirtualKeyCode::Key2, // 2
+    VirtualKeyCode::Key3, // 3
+    VirtualKeyCode::Q,    // 4
+    VirtualKeyCode::W,    // 5
+    VirtualKeyCode::E,    // 6
+    VirtualKeyCode::A,    // 7
+    VirtualKeyCode::S,    // 8
+    VirtualKeyCode::D,    // 9
+    VirtualKeyCode::Z,    // A
+    VirtualKeyCode::C,    // B
+    VirtualKeyCode::Key4, // C
+    VirtualKeyCode::R,    // D
+    VirtualKeyCode::F,    // E
+    VirtualKeyCode::V,    // F
+];
 
-    let toggle_pause = input.key_pressed(VirtualKeyCode::P);
+/// Default keymap with the given CHIP-8 keys (hex digits) rebound.
+pub fn keymap_with_overrides(overrides: &HashMap<String, VirtualKeyCode>) -> KeyMap {
+    let mut keymap = DEFAULT_KEYMAP;
+    for (key, keycode) in overrides {
+        match u8::from_str_radix(key, 16) {
+            Ok(key) if key < 16 => keymap[key as usize] = *keycode,
+            _ => eprintln!("[Input] Ignoring binding for invalid key `{}`", key),
+        }
+    }
+    keymap
+}
 
-    let keys = [
-        key_0,
-        key_1,
-        key_2,
-        key_3,
-        key_4,
-        key_5,
-        key_6,
-        key_7,
-        key_8,
-        key_9,
-        key_a,
-        key_b,
-        key_c,
-        key_d,
-        key_e,
-        key_f,
-    ];
+pub fn get_processed_input(input: &WinitInputHelper, keymap: &KeyMap) -> (Keys, Hotkeys) {
+    let keys = keymap.map(|keycode| input.key_held(keycode));
+    let hotkeys = Hotkeys {
+        toggle_pause: input.key_pressed(VirtualKeyCode::P),
+        cycle_palette: input.key_pressed(VirtualKeyCode::F2),
+        cycle_filter: input.key_pressed(VirtualKeyCode::F3),
+        screenshot: input.key_pressed(VirtualKeyCode::F12),
+        toggle_recording: input.key_pressed(VirtualKeyCode::F10),
+        toggle_mute: input.key_pressed(VirtualKeyCode::M),
+        volume_down: input.key_pressed(VirtualKeyCode::Minus)
+            || input.key_pressed(VirtualKeyCode::NumpadSubtract),
+        volume_up: input.key_pressed(VirtualKeyCode::Equals)
+            || input.key_pressed(VirtualKeyCode::NumpadAdd),
+        toggle_browser: input.key_pressed(VirtualKeyCode::F1),
+        reset: input.key_pressed(VirtualKeyCode::F5) && !input.held_shift(),
+        hard_reset: input.key_pressed(VirtualKeyCode::F5) && input.held_shift(),
+        slow_down: input.key_pressed(VirtualKeyCode::F6),
+        speed_up: input.key_pressed(VirtualKeyCode::F7),
+        normal_speed: input.key_pressed(VirtualKeyCode::F8),
+        advance_frame: input.key_pressed(VirtualKeyCode::F9),
+    };
 
-    (keys, toggle_pause)
+    (keys, hotkeys)
 }
@@ -1,5 +1,8 @@
+use super::quirks::Platform;
+
 pub type Address = usize;
 
+#[allow(clippy::upper_case_acronyms)]
 pub enum Instruction {
     CLS,
     RET,
@@ -17,9 +20,9 @@ pub enum Instruction {
     XORVxVy(usize, usize),
     ADDVxVy(usize, usize),
     SUBVxVy(usize, usize),
-    SHRVx(usize),
+    SHRVxVy(usize, usize),
     SUBNVxVy(usize, usize),
-    SHLVx(usize),
+    SHLVxVy(usize, usize),
     SNEVxVy(usize, usize),
     LDI(Address),
     JP0(Address),
@@ -36,13 +39,50 @@ pub enum Instruction {
     LDBVx(usize),
     LDIVx(usize),
     LDVxMem(usize),
+    /// XO-CHIP: select the bitplanes drawn to
+    PLANE(u8),
+    /// SUPER-CHIP: scroll down N pixels
+    SCD(u8),
+    /// XO-CHIP: scroll up N pixels
+    SCU(u8),
+    /// SUPER-CHIP: scroll right 4 pixels
+    SCR,
+    /// SUPER-CHIP: scroll left 4 pixels
+    SCL,
+    /// SUPER-CHIP: exit the interpreter
+    EXIT,
+    /// SUPER-CHIP: switch to the 64x32 screen
+    LOW,
+    /// SUPER-CHIP: switch to the 128x64 screen
+    HIGH,
+    /// SUPER-CHIP: point I at the big (8x10) digit in VX
+    LDHFVx(usize),
+    /// SUPER-CHIP: save V0 to VX in the user flags
+    LDRVx(usize),
+    /// SUPER-CHIP: load V0 to VX from the user flags
+    LDVxR(usize),
+    /// XO-CHIP: save VX to VY at I, leaving I as it is
+    SAVEVxVy(usize, usize),
+    /// XO-CHIP: load VX to VY from I, leaving I as it is
+    LOADVxVy(usize, usize),
+    /// XO-CHIP: load I with the address in the next two bytes
+    LDILong,
+    /// XO-CHIP: load the 16 byte audio pattern from I
+    AUDIO,
+    /// XO-CHIP: set the audio pattern's playback rate from VX
+    PITCHVx(usize),
     Unknown,
 }
 
 impl Instruction {
-    pub fn from_opcode(opcode: u16) -> Self {
+    /// Decodes `opcode` as `platform` reads it: extended opcodes are only
+    /// decoded on the platforms that have them.
+    pub fn from_opcode(opcode: u16, platform: Platform) -> Self {
         use Instruction::*;
 
+        let super_chip = platform != Platform::Chip8;
+        let xo_chip = platform == Platform::XoChip;
+
         let first_nibble = (opcode >> 12) & 0xF;
         let x = ((opcode >> 8) & 0xF) as usize;
         let y = ((opcode >> 4) & 0xF) as usize;
@@ -53,13 +93,24 @@ impl Instruction {
             0x0 => match opcode {
                 0x00E0 => CLS,
                 0x00EE => RET,
+                0x00FB if super_chip => SCR,
+                0x00FC if super_chip => SCL,
+                0x00FD if super_chip => EXIT,
+                0x00FE if super_chip => LOW,
+                0x00FF if super_chip => HIGH,
+                _ if super_chip && opcode & 0xFFF0 == 0x00C0 => SCD((opcode & 0xF) as u8),
+                _ if xo_chip && opcode & 0xFFF0 == 0x00D0 => SCU((opcode & 0xF) as u8),
                 _ => SYS(nnn),
             },
             0x1 => JP(nnn),
             0x2 => CALL(nnn),
             0x3 => SEVxByte(x, kk),
             0x4 => SNEVxByte(x, kk),
-            0x5 => SEVxVy(x, y),
+            0x5 => match opcode & 0xF {
+                0x2 if xo_chip => SAVEVxVy(x, y),
+                0x3 if xo_chip => LOADVxVy(x, y),
+                _ => SEVxVy(x, y),
+            },
             0x6 => LDVxByte(x, kk),
             0x7 => ADDVxByte(x, kk),
             0x8 => {
@@ -71,9 +122,9 @@ impl Instruction {
                     0x3 => XORVxVy(x, y),
                     0x4 => ADDVxVy(x, y),
                     0x5 => SUBVxVy(x, y),
-                    0x6 => SHRVx(x),
+                    0x6 => SHRVxVy(x, y),
                     0x7 => SUBNVxVy(x, y),
-                    0xE => SHLVx(x),
+                    0xE => SHLVxVy(x, y),
                     _ => Unknown,
                 }
             }
@@ -88,6 +139,8 @@ impl Instruction {
                 _ => Unknown,
             },
             0xF => match kk {
+                0x00 if xo_chip && x == 0 => LDILong,
+                0x02 if xo_chip && x == 0 => AUDIO,
                 0x07 => LDVxDT(x),
                 0x0A => LDVxK(x),
                 0x15 => LDDTVx(x),
@@ -97,6 +150,11 @@ impl Instruction {
                 0x33 => LDBVx(x),
                 0x55 => LDIVx(x),
                 0x65 => LDVxMem(x),
+                0x01 if xo_chip => PLANE(x as u8),
+                0x30 if super_chip => LDHFVx(x),
+                0x3A if xo_chip => PITCHVx(x),
+                0x75 if super_chip => LDRVx(x),
+                0x85 if super_chip => LDVxR(x),
                 _ => Unknown,
             },
             _ => Unknown,
@@ -109,28 +167,30 @@ impl Instruction {
         match self {
             CLS => "CLS".to_string(),
             RET => "RET".to_string(),
-            SYS(address) => format!("SYS {:03X}", address),
-            JP(address) => format!("JP {:03X}", address),
-            CALL(address) => format!("CALL {:03X}", address),
-            SEVxByte(x, byte) => format!("SE V{:X}, {:02X}", x, byte),
-            SNEVxByte(x, byte) => format!("SNE V{:X}, {:02X}", x, byte),
+            SYS(address) => format!("SYS 0x{:03X}", address),
+            JP(address) => format!("JP 0x{:03X}", address),
+            CALL(address) => format!("CALL 0x{:03X}", address),
+            SEVxByte(x, byte) => format!("SE V{:X}, 0x{:02X}", x, byte),
+            SNEVxByte(x, byte) => format!("SNE V{:X}, 0x{:02X}", x, byte),
             SEVxVy(x, y) => format!("SE V{:X}, V{:X}", x, y),
-            LDVxByte(x, byte) => format!("LD V{:X}, {:02X}", x, byte),
-            ADDVxByte(x, byte) => format!("ADD V{:X}, {:02X}", x, byte),
+            LDVxByte(x, byte) => format!("LD V{:X}, 0x{:02X}", x, byte),
+            ADDVxByte(x, byte) => format!("ADD V{:X}, 0x{:02X}", x, byte),
             LDVxVy(x, y) => format!("LD V{:X}, V{:X}", x, y),
             ORVxVy(x, y) => format!("OR V{:X}, V{:X}", x, y),
             ANDVxVy(x, y) => format!("AND V{:X}, V{:X}", x, y),
             XORVxVy(x, y) => format!("XOR V{:X}, V{:X}", x, y),
             ADDVxVy(x, y) => format!("ADD V{:X}, V{:X}", x, y),
             SUBVxVy(x, y) => format!("SUB V{:X}, V{:X}", x, y),
-            SHRVx(x) => format!("SHR V{:X}", x),
+            SHRVxVy(x, 0) => format!("SHR V{:X}", x),
+            SHRVxVy(x, y) => format!("SHR V{:X}, V{:X}", x, y),
             SUBNVxVy(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
-            SHLVx(x) => format!("SHL V{:X}", x),
+            SHLVxVy(x, 0) => format!("SHL V{:X}", x),
+            SHLVxVy(x, y) => format!("SHL V{:X}, V{:X}", x, y),
             SNEVxVy(x, y) => format!("SNE V{:X}, V{:X}", x, y),
-            LDI(address) => format!("LD I, {:03X}", address),
-            JP0(address) => format!("JP V0, {:03X}", address),
-            RNDVxByte(x, byte) => format!("RND V{:X}, {:02X}", x, byte),
-            DRWVxVyNibble(x, y, n) => format!("DRW V{:X}, V{:X}, {:X}", x, y, n),
+            LDI(address) => format!("LD I, 0x{:03X}", address),
+            JP0(address) => format!("JP V0, 0x{:03X}", address),
+            RNDVxByte(x, byte) => format!("RND V{:X}, 0x{:02X}", x, byte),
+            DRWVxVyNibble(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
             SKPVx(x) => format!("SKP V{:X}", x),
             SKNPVx(x) => format!("SKNP V{:X}", x),
             LDVxDT(x) => format!("LD V{:X}, DT", x),
@@ -142,7 +202,83 @@ impl Instruction {
             LDBVx(x) => format!("LD B, V{:X}", x),
             LDIVx(x) => format!("LD [I], V{:X}", x),
             LDVxMem(x) => format!("LD V{:X}, [I]", x),
+            PLANE(planes) => format!("PLANE {}", planes),
+            SCD(n) => format!("SCD {}", n),
+            SCU(n) => format!("SCU {}", n),
+            SCR => "SCR".to_string(),
+            SCL => "SCL".to_string(),
+            EXIT => "EXIT".to_string(),
+            LOW => "LOW".to_string(),
+            HIGH => "HIGH".to_string(),
+            LDHFVx(x) => format!("LD HF, V{:X}", x),
+            LDRVx(x) => format!("LD R, V{:X}", x),
+            LDVxR(x) => format!("LD V{:X}, R", x),
+            SAVEVxVy(x, y) => format!("SAVE V{:X}, V{:X}", x, y),
+            LOADVxVy(x, y) => format!("LOAD V{:X}, V{:X}", x, y),
+            LDILong => "LD I, LONG".to_string(),
+            AUDIO => "AUDIO".to_string(),
+            PITCHVx(x) => format!("LD PITCH, V{:X}", x),
             Unknown => "Unknown".to_string(),
         }
     }
+
+    /// The instruction's form, with operands replaced by placeholders.
+    pub fn pattern(&self) -> &'static str {
+        use Instruction::*;
+
+        match self {
+            CLS => "CLS",
+            RET => "RET",
+            SYS(_) => "SYS addr",
+            JP(_) => "JP addr",
+            CALL(_) => "CALL addr",
+            SEVxByte(..) => "SE Vx, byte",
+            SNEVxByte(..) => "SNE Vx, byte",
+            SEVxVy(..) => "SE Vx, Vy",
+            LDVxByte(..) => "LD Vx, byte",
+            ADDVxByte(..) => "ADD Vx, byte",
+            LDVxVy(..) => "LD Vx, Vy",
+            ORVxVy(..) => "OR Vx, Vy",
+            ANDVxVy(..) => "AND Vx, Vy",
+            XORVxVy(..) => "XOR Vx, Vy",
+            ADDVxVy(..) => "ADD Vx, Vy",
+            SUBVxVy(..) => "SUB Vx, Vy",
+            SHRVxVy(..) => "SHR Vx {, Vy}",
+            SUBNVxVy(..) => "SUBN Vx, Vy",
+            SHLVxVy(..) => "SHL Vx {, Vy}",
+            SNEVxVy(..) => "SNE Vx, Vy",
+            LDI(_) => "LD I, addr",
+            JP0(_) => "JP V0, addr",
+            RNDVxByte(..) => "RND Vx, byte",
+            DRWVxVyNibble(..) => "DRW Vx, Vy, nibble",
+            SKPVx(_) => "SKP Vx",
+            SKNPVx(_) => "SKNP Vx",
+            LDVxDT(_) => "LD Vx, DT",
+            LDVxK(_) => "LD Vx, K",
+            LDDTVx(_) => "LD DT, Vx",
+            LDSTVx(_) => "LD ST, Vx",
+            ADDIVx(_) => "ADD I, Vx",
+            LDFVx(_) => "LD F, Vx",
+            LDBVx(_) => "LD B, Vx",
+            LDIVx(_) => "LD [I], Vx",
+            LDVxMem(_) => "LD Vx, [I]",
+            PLANE(_) => "PLANE n",
+            SCD(_) => "SCD nibble",
+            SCU(_) => "SCU nibble",
+            SCR => "SCR",
+            SCL => "SCL",
+            EXIT => "EXIT",
+            LOW => "LOW",
+            HIGH => "HIGH",
+            LDHFVx(_) => "LD HF, Vx",
+            LDRVx(_) => "LD R, Vx",
+            LDVxR(_) => "LD Vx, R",
+            SAVEVxVy(..) => "SAVE Vx, Vy",
+            LOADVxVy(..) => "LOAD Vx, Vy",
+            LDILong => "LD I, LONG",
+            AUDIO => "AUDIO",
+            PITCHVx(_) => "LD PITCH, Vx",
+            Unknown => "Unknown",
+        }
+    }
 }
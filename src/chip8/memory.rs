@@ -0,0 +1,36 @@
+use rand::{rngs::StdRng, Rng};
+use std::str::FromStr;
+
+/// What memory outside the font and ROM holds after loading a ROM or a
+/// hard reset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MemoryFill {
+    #[default]
+    Zero,
+    /// Random bytes, to catch ROMs that rely on uninitialized memory
+    Random,
+}
+
+impl MemoryFill {
+    pub fn fill(&self, memory: &mut [u8], rng: &mut StdRng) {
+        match self {
+            MemoryFill::Zero => memory.fill(0),
+            MemoryFill::Random => rng.fill(memory),
+        }
+    }
+}
+
+impl FromStr for MemoryFill {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "zero" => Ok(MemoryFill::Zero),
+            "random" => Ok(MemoryFill::Random),
+            _ => Err(format!(
+                "unknown memory fill `{}` (expected zero or random)",
+                name
+            )),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use super::Rgba;
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+
+/// Display colors, indexed by pixel value: background, plane 1, plane 2 and
+/// both planes (the last two are only used by XO-CHIP).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub name: &'static str,
+    pub colors: [Rgba; 4],
+}
+
+pub const PALETTES: [Palette; 5] = [
+    Palette {
+        name: "default",
+        colors: [
+            [0x11, 0x11, 0x11, 0xff],
+            [0x5e, 0x48, 0xe8, 0xff],
+            [0xe8, 0x48, 0x8e, 0xff],
+            [0xf2, 0xf2, 0xf2, 0xff],
+        ],
+    },
+    Palette {
+        name: "green",
+        colors: [
+            [0x0a, 0x14, 0x0a, 0xff],
+            [0x33, 0xff, 0x66, 0xff],
+            [0x1a, 0x80, 0x33, 0xff],
+            [0xb3, 0xff, 0xc6, 0xff],
+        ],
+    },
+    Palette {
+        name: "amber",
+        colors: [
+            [0x1a, 0x0f, 0x00, 0xff],
+            [0xff, 0xb0, 0x00, 0xff],
+            [0x99, 0x5c, 0x00, 0xff],
+            [0xff, 0xe0, 0x99, 0xff],
+        ],
+    },
+    Palette {
+        name: "lcd",
+        colors: [
+            [0x9b, 0xbc, 0x0f, 0xff],
+            [0x0f, 0x38, 0x0f, 0xff],
+            [0x30, 0x62, 0x30, 0xff],
+            [0x6a, 0x8c, 0x1f, 0xff],
+        ],
+    },
+    Palette {
+        name: "contrast",
+        colors: [
+            [0x00, 0x00, 0x00, 0xff],
+            [0xff, 0xff, 0xff, 0xff],
+            [0xff, 0xff, 0x00, 0xff],
+            [0x00, 0xff, 0xff, 0xff],
+        ],
+    },
+];
+
+impl Palette {
+    pub fn by_name(name: &str) -> Option<Palette> {
+        PALETTES
+            .iter()
+            .find(|palette| palette.name.eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// The preset following this one, wrapping around.
+    pub fn next(&self) -> Palette {
+        let i = PALETTES
+            .iter()
+            .position(|palette| palette.name == self.name)
+            .unwrap_or(0);
+        PALETTES[(i + 1) % PALETTES.len()]
+    }
+
+    pub fn color(&self, pixel: u8) -> Rgba {
+        self.colors[pixel as usize & 0b11]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        PALETTES[0]
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Palette::by_name(name).ok_or_else(|| {
+            let names: Vec<_> = PALETTES.iter().map(|palette| palette.name).collect();
+            format!(
+                "unknown palette `{}` (expected one of: {})",
+                name,
+                names.join(", ")
+            )
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Palette {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,174 @@
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// The CHIP-8 dialect a ROM was written for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    #[default]
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "chip8",
+            Platform::SuperChip => "superchip",
+            Platform::XoChip => "xochip",
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks {
+                vf_reset: true,
+                memory: true,
+                jumping: false,
+                display_wait: true,
+                clipping: true,
+                shifting: false,
+            },
+            Platform::SuperChip => Quirks {
+                vf_reset: false,
+                memory: false,
+                jumping: true,
+                display_wait: false,
+                clipping: true,
+                shifting: true,
+            },
+            Platform::XoChip => Quirks {
+                vf_reset: false,
+                memory: true,
+                jumping: false,
+                display_wait: false,
+                clipping: false,
+                shifting: false,
+            },
+        }
+    }
+
+    /// Instructions executed per frame (60 frames per second)
+    pub fn speed(&self) -> u32 {
+        match self {
+            Platform::Chip8 => 10,
+            Platform::SuperChip => 30,
+            Platform::XoChip => 100,
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().replace('-', "").as_str() {
+            "chip8" => Ok(Platform::Chip8),
+            "superchip" | "schip" => Ok(Platform::SuperChip),
+            "xochip" => Ok(Platform::XoChip),
+            _ => Err(format!(
+                "unknown platform `{}` (expected chip8, superchip or xochip)",
+                name
+            )),
+        }
+    }
+}
+
+/// Behaviors that differ between CHIP-8 interpreters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY1`, `8XY2` and `8XY3` reset VF to zero
+    pub vf_reset: bool,
+    /// `FX55` and `FX65` increment I by X + 1
+    pub memory: bool,
+    /// `BNNN` jumps to NNN + VX instead of NNN + V0
+    pub jumping: bool,
+    /// `DXYN` waits for the next frame, so at most one sprite is drawn per
+    /// frame
+    pub display_wait: bool,
+    /// Sprites are cut off at the edges of the screen instead of wrapping
+    /// around to the other side
+    pub clipping: bool,
+    /// `8XY6` and `8XYE` shift VX in place instead of shifting VY into VX
+    pub shifting: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Platform::default().quirks()
+    }
+}
+
+/// Per-ROM quirk settings, each one overriding the platform default when set.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuirkOverrides {
+    pub vf_reset: Option<bool>,
+    pub memory: Option<bool>,
+    pub jumping: Option<bool>,
+    pub display_wait: Option<bool>,
+    pub clipping: Option<bool>,
+    pub shifting: Option<bool>,
+}
+
+impl QuirkOverrides {
+    pub fn apply(&self, quirks: &mut Quirks) {
+        if let Some(vf_reset) = self.vf_reset {
+            quirks.vf_reset = vf_reset;
+        }
+        if let Some(memory) = self.memory {
+            quirks.memory = memory;
+        }
+        if let Some(jumping) = self.jumping {
+            quirks.jumping = jumping;
+        }
+        if let Some(display_wait) = self.display_wait {
+            quirks.display_wait = display_wait;
+        }
+        if let Some(clipping) = self.clipping {
+            quirks.clipping = clipping;
+        }
+        if let Some(shifting) = self.shifting {
+            quirks.shifting = shifting;
+        }
+    }
+}
+
+/// Parses a comma-separated list of `quirk` or `quirk=on|off`, e.g.
+/// `jumping,vf_reset=off`.
+impl FromStr for QuirkOverrides {
+    type Err = String;
+
+    fn from_str(list: &str) -> Result<Self, Self::Err> {
+        let mut overrides = QuirkOverrides::default();
+        for item in list
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+        {
+            let (name, value) = item.split_once('=').unwrap_or((item, "on"));
+            let value = match value.to_ascii_lowercase().as_str() {
+                "on" | "true" | "1" => true,
+                "off" | "false" | "0" => false,
+                _ => return Err(format!("invalid value `{}` for quirk `{}`", value, name)),
+            };
+            let quirk = match name.to_ascii_lowercase().replace('-', "_").as_str() {
+                "vf_reset" => &mut overrides.vf_reset,
+                "memory" => &mut overrides.memory,
+                "jumping" => &mut overrides.jumping,
+                "display_wait" => &mut overrides.display_wait,
+                "clipping" => &mut overrides.clipping,
+                "shifting" => &mut overrides.shifting,
+                _ => {
+                    return Err(format!(
+                        "unknown quirk `{}` (expected vf_reset, memory, jumping, display_wait, clipping or shifting)",
+                        name
+                    ))
+                }
+            };
+            *quirk = Some(value);
+        }
+        Ok(overrides)
+    }
+}
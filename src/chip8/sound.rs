@@ -0,0 +1,85 @@
+/// When the buzzer was on during one emulated frame, with sub-frame
+/// precision, so a beep lasts exactly as long as the sound timer says.
+#[derive(Clone, Debug, Default)]
+pub struct SoundFrame {
+    /// Whether the buzzer was on when the frame started
+    start: bool,
+    /// Changes during the frame, in order
+    events: Vec<SoundEvent>,
+    /// What XO-CHIP ROMs asked the buzzer to play instead of its own tone
+    pattern: Option<AudioPattern>,
+}
+
+/// XO-CHIP's sound: a loop of 128 one-bit samples, played at a rate set by
+/// the pitch register.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioPattern {
+    bits: [u8; 16],
+    /// Samples per second
+    rate: f32,
+}
+
+impl AudioPattern {
+    pub fn new(bits: [u8; 16], pitch: u8) -> Self {
+        Self {
+            bits,
+            rate: 4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0),
+        }
+    }
+
+    /// How many times a second the whole loop plays.
+    pub fn loops_per_second(&self) -> f32 {
+        self.rate / 128.0
+    }
+
+    /// The sample at `position` through the loop, from 0 to 1, as -1 or 1.
+    pub fn sample(&self, position: f32) -> f32 {
+        let i = (position * 128.0) as usize % 128;
+        if self.bits[i / 8] & (0x80 >> (i % 8)) != 0 {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct SoundEvent {
+    /// Fraction of the frame elapsed, from 0 to 1
+    time: f32,
+    active: bool,
+}
+
+impl SoundFrame {
+    /// Starts recording a new frame.
+    pub(super) fn begin(&mut self, active: bool) {
+        self.start = active;
+        self.events.clear();
+    }
+
+    /// Records the buzzer state at `time` (fraction of the frame), if it
+    /// changed.
+    pub(super) fn record(&mut self, time: f32, active: bool) {
+        if self.active_at(1.0) != active {
+            self.events.push(SoundEvent { time, active });
+        }
+    }
+
+    pub(super) fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        self.pattern = pattern;
+    }
+
+    /// The XO-CHIP audio pattern the buzzer plays, if the ROM loaded one.
+    pub fn pattern(&self) -> Option<AudioPattern> {
+        self.pattern
+    }
+
+    /// Whether the buzzer was on at `time`, from 0 (start of the frame) to 1.
+    pub fn active_at(&self, time: f32) -> bool {
+        self.events
+            .iter()
+            .take_while(|event| event.time <= time)
+            .last()
+            .map_or(self.start, |event| event.active)
+    }
+}
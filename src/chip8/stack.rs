@@ -0,0 +1,130 @@
+use std::{error::Error, fmt};
+
+/// Nested calls allowed by most interpreters
+pub const DEFAULT_STACK_DEPTH: usize = 16;
+/// Nested calls that fit in the COSMAC VIP's stack
+pub const VIP_STACK_DEPTH: usize = 12;
+/// End of the COSMAC VIP's stack in memory, which grows down from here
+const VIP_STACK_END: usize = 0xED0;
+
+/// A `CALL` or `RET` that the stack can't hold, with the address of the
+/// instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackError {
+    Overflow { address: usize, depth: usize },
+    Underflow { address: usize },
+}
+
+impl fmt::Display for StackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StackError::Overflow { address, depth } => write!(
+                f,
+                "stack overflow at 0x{:03X}: more than {} nested calls",
+                address, depth
+            ),
+            StackError::Underflow { address } => write!(
+                f,
+                "stack underflow at 0x{:03X}: return without a call",
+                address
+            ),
+        }
+    }
+}
+
+impl Error for StackError {}
+
+/// Return addresses of the subroutines being run, either kept apart from
+/// memory or, like on the VIP, in memory where ROMs can overwrite them.
+pub struct Stack {
+    entries: Vec<usize>,
+    depth: usize,
+    in_memory: bool,
+}
+
+impl Stack {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![],
+            depth: DEFAULT_STACK_DEPTH,
+            in_memory: false,
+        }
+    }
+
+    /// Allows `depth` nested calls, at most `VIP_STACK_DEPTH` in memory.
+    pub fn configure(&mut self, depth: usize, in_memory: bool) {
+        self.depth = if in_memory {
+            depth.min(VIP_STACK_DEPTH)
+        } else {
+            depth
+        };
+        self.in_memory = in_memory;
+        self.entries.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// The return addresses, innermost last.
+    pub fn entries(&self, memory: &[u8]) -> Vec<usize> {
+        if !self.in_memory {
+            return self.entries.clone();
+        }
+        (0..self.entries.len())
+            .map(|i| Self::read_slot(memory, i))
+            .collect()
+    }
+
+    /// Pushes the return address of the `CALL` at `address`.
+    pub fn push(
+        &mut self,
+        address: usize,
+        return_address: usize,
+        memory: &mut [u8],
+    ) -> Result<(), StackError> {
+        if self.entries.len() >= self.depth {
+            return Err(StackError::Overflow {
+                address,
+                depth: self.depth,
+            });
+        }
+        if self.in_memory {
+            let slot = Self::slot(self.entries.len());
+            memory[slot..slot + 2].copy_from_slice(&(return_address as u16).to_be_bytes());
+        }
+        self.entries.push(return_address);
+        Ok(())
+    }
+
+    /// Pops the return address for the `RET` at `address`.
+    pub fn pop(&mut self, address: usize, memory: &[u8]) -> Result<usize, StackError> {
+        let return_address = self
+            .entries
+            .pop()
+            .ok_or(StackError::Underflow { address })?;
+        if self.in_memory {
+            return Ok(Self::read_slot(memory, self.entries.len()));
+        }
+        Ok(return_address)
+    }
+
+    /// Memory address of the innermost return address, if the stack is in
+    /// memory and not empty.
+    pub fn top_slot(&self) -> Option<usize> {
+        let top = self.entries.len().checked_sub(1)?;
+        self.in_memory.then(|| Self::slot(top))
+    }
+
+    /// Memory address of entry `i` of the in-memory stack.
+    fn slot(i: usize) -> usize {
+        VIP_STACK_END - 2 * (i + 1)
+    }
+
+    /// Entry `i` of the in-memory stack, cut down to an address in the
+    /// VIP's 4 KB, whatever the ROM wrote there.
+    fn read_slot(memory: &[u8], i: usize) -> usize {
+        let slot = Self::slot(i);
+        u16::from_be_bytes([memory[slot], memory[slot + 1]]) as usize & 0xFFF
+    }
+}
@@ -0,0 +1,106 @@
+use super::{parse_number, Keys, Registers, Screen, MEMORY_SIZE};
+use std::{fmt, str::FromStr};
+
+/// A read-only view of the machine, for debuggers and other tools.
+pub struct MachineState<'a> {
+    pub v: Registers,
+    pub index: usize,
+    pub pc: usize,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// Return addresses of the subroutines being run, innermost last
+    pub stack: Vec<usize>,
+    pub memory: &'a [u8],
+    pub screen: &'a Screen,
+    pub keys: Keys,
+}
+
+impl MachineState<'_> {
+    /// The field's value; registers and addresses that don't exist read as
+    /// 0.
+    pub fn get(&self, field: Field) -> usize {
+        match field {
+            Field::Register(x) => self.v.get(x).map_or(0, |&v| v as usize),
+            Field::Index => self.index,
+            Field::ProgramCounter => self.pc,
+            Field::DelayTimer => self.delay_timer as usize,
+            Field::SoundTimer => self.sound_timer as usize,
+            Field::Memory(address) => self.memory.get(address).map_or(0, |&v| v as usize),
+        }
+    }
+}
+
+/// A value in the machine that can be read and written, written as `V0` to
+/// `VF`, `I`, `PC`, `DT`, `ST` or `[ADDR]` for a byte of memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Register(usize),
+    Index,
+    ProgramCounter,
+    DelayTimer,
+    SoundTimer,
+    Memory(usize),
+}
+
+impl Field {
+    /// `VX`, if there is such a register.
+    pub fn register(x: usize) -> Option<Self> {
+        (x < 16).then_some(Field::Register(x))
+    }
+
+    /// The byte at `address`, if it is in memory.
+    pub fn memory(address: usize) -> Option<Self> {
+        (address < MEMORY_SIZE).then_some(Field::Memory(address))
+    }
+
+    /// The largest value the field holds.
+    pub fn max(&self) -> usize {
+        match self {
+            Field::Index | Field::ProgramCounter => MEMORY_SIZE - 1,
+            _ => 0xFF,
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Field::Register(x) => write!(f, "V{:X}", x),
+            Field::Index => write!(f, "I"),
+            Field::ProgramCounter => write!(f, "PC"),
+            Field::DelayTimer => write!(f, "DT"),
+            Field::SoundTimer => write!(f, "ST"),
+            Field::Memory(address) => write!(f, "[0x{:03X}]", address),
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let name = text.trim().to_ascii_uppercase();
+        let field = match name.as_str() {
+            "I" => Field::Index,
+            "PC" => Field::ProgramCounter,
+            "DT" => Field::DelayTimer,
+            "ST" => Field::SoundTimer,
+            _ => {
+                let register = name
+                    .strip_prefix('V')
+                    .filter(|hex| hex.len() == 1)
+                    .and_then(|hex| usize::from_str_radix(hex, 16).ok())
+                    .and_then(Field::register);
+                let address = name
+                    .strip_prefix('[')
+                    .and_then(|rest| rest.strip_suffix(']'))
+                    .and_then(parse_number)
+                    .and_then(Field::memory);
+                register
+                    .or(address)
+                    .ok_or_else(|| format!("unknown target `{}`", name))?
+            }
+        };
+        Ok(field)
+    }
+}
@@ -0,0 +1,994 @@
+//! Arithmetic and flag semantics, after Timendus' flags test ROM: each op is
+//! checked for its result and VF, including with VF itself as VX. Also the
+//! bounds of the call stack, what resets leave in memory, sprites at the
+//! edges of the screen and on XO-CHIP's planes, the events fired to hooks
+//! and the writes they ask for, the assembler reading back what the
+//! disassembler writes, and what instructions cost under the VIP timing and
+//! the display wait. Also the
+//! beeper's waveform, how it fades in and out, and how frames of it are
+//! queued for the output device, the frame count patched into APNG
+//! recordings, and the layout of audio and video recordings. And last, the
+//! ROM database and the order settings are picked in.
+
+use super::{
+    assemble,
+    audio::{queue_frame, Tone},
+    av::AvRecorder,
+    capture::{patch_frame_count, scale, start_apng},
+    disassembler::disassemble,
+    instruction::Instruction,
+    stack::StackError,
+    Beeper, Chip8, Database, Field, Hook, MachineState, MemoryFill, Platform, QuirkOverrides,
+    SoundFrame, Timing, Waveform, DEFAULT_STACK_DEPTH, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+use rtrb::RingBuffer;
+use std::{cell::RefCell, fs, io::Cursor, rc::Rc};
+
+/// Assembles `source`, followed by an endless loop, and runs it to the loop.
+fn run(source: &str, platform: Platform) -> Chip8 {
+    let rom = assemble(&format!("{}\nend: JP end", source)).unwrap();
+    let mut chip8 = Chip8::new(Database::builtin());
+    chip8.set_platform(platform);
+    chip8.load_rom(&rom).unwrap();
+    let end = 0x200 + rom.len() - 2;
+    while chip8.state().pc != end {
+        chip8.step(false).unwrap();
+    }
+    chip8
+}
+
+fn run_chip8(source: &str) -> Chip8 {
+    run(source, Platform::Chip8)
+}
+
+#[test]
+fn add_sets_carry() {
+    let chip8 = run_chip8("LD V0, 0xFF\nLD V1, 0x02\nADD V0, V1");
+    assert_eq!(chip8.state().v[0], 0x01);
+    assert_eq!(chip8.state().v[0xF], 1);
+
+    let chip8 = run_chip8("LD V0, 0x10\nLD V1, 0x20\nLD VF, 5\nADD V0, V1");
+    assert_eq!(chip8.state().v[0], 0x30);
+    assert_eq!(chip8.state().v[0xF], 0);
+}
+
+#[test]
+fn sub_sets_not_borrow() {
+    let chip8 = run_chip8("LD V0, 5\nLD V1, 3\nSUB V0, V1");
+    assert_eq!(chip8.state().v[0], 2);
+    assert_eq!(chip8.state().v[0xF], 1);
+
+    let chip8 = run_chip8("LD V0, 3\nLD V1, 5\nSUB V0, V1");
+    assert_eq!(chip8.state().v[0], 0xFE);
+    assert_eq!(chip8.state().v[0xF], 0);
+
+    let chip8 = run_chip8("LD V0, 5\nLD V1, 5\nSUB V0, V1");
+    assert_eq!(chip8.state().v[0], 0);
+    assert_eq!(chip8.state().v[0xF], 1);
+}
+
+#[test]
+fn subn_sets_not_borrow() {
+    let chip8 = run_chip8("LD V0, 3\nLD V1, 5\nSUBN V0, V1");
+    assert_eq!(chip8.state().v[0], 2);
+    assert_eq!(chip8.state().v[0xF], 1);
+
+    let chip8 = run_chip8("LD V0, 5\nLD V1, 3\nSUBN V0, V1");
+    assert_eq!(chip8.state().v[0], 0xFE);
+    assert_eq!(chip8.state().v[0xF], 0);
+
+    let chip8 = run_chip8("LD V0, 5\nLD V1, 5\nSUBN V0, V1");
+    assert_eq!(chip8.state().v[0], 0);
+    assert_eq!(chip8.state().v[0xF], 1);
+}
+
+#[test]
+fn shr_sets_bit_0() {
+    let chip8 = run_chip8("LD V1, 0x05\nSHR V0, V1");
+    assert_eq!(chip8.state().v[0], 0x02);
+    assert_eq!(chip8.state().v[0xF], 1);
+
+    let chip8 = run_chip8("LD V1, 0x04\nSHR V0, V1");
+    assert_eq!(chip8.state().v[0], 0x02);
+    assert_eq!(chip8.state().v[0xF], 0);
+}
+
+#[test]
+fn shl_sets_bit_7() {
+    let chip8 = run_chip8("LD V1, 0x81\nSHL V0, V1");
+    assert_eq!(chip8.state().v[0], 0x02);
+    assert_eq!(chip8.state().v[0xF], 1);
+
+    let chip8 = run_chip8("LD V1, 0x41\nSHL V0, V1");
+    assert_eq!(chip8.state().v[0], 0x82);
+    assert_eq!(chip8.state().v[0xF], 0);
+}
+
+#[test]
+fn shifting_quirk_shifts_vx_in_place() {
+    let chip8 = run("LD V0, 0x81\nLD V1, 0x10\nSHL V0, V1", Platform::SuperChip);
+    assert_eq!(chip8.state().v[0], 0x02);
+    assert_eq!(chip8.state().v[1], 0x10);
+    assert_eq!(chip8.state().v[0xF], 1);
+
+    let chip8 = run("LD V0, 0x81\nLD V1, 0x10\nSHR V0, V1", Platform::SuperChip);
+    assert_eq!(chip8.state().v[0], 0x40);
+    assert_eq!(chip8.state().v[0xF], 1);
+}
+
+#[test]
+fn flag_wins_when_vx_is_vf() {
+    let chip8 = run_chip8("LD VF, 0xFF\nLD V1, 0x02\nADD VF, V1");
+    assert_eq!(chip8.state().v[0xF], 1);
+
+    let chip8 = run_chip8("LD VF, 0x10\nLD V1, 0x20\nADD VF, V1");
+    assert_eq!(chip8.state().v[0xF], 0);
+
+    let chip8 = run_chip8("LD VF, 5\nLD V1, 3\nSUB VF, V1");
+    assert_eq!(chip8.state().v[0xF], 1);
+
+    let chip8 = run_chip8("LD VF, 5\nLD V1, 3\nSUBN VF, V1");
+    assert_eq!(chip8.state().v[0xF], 0);
+
+    let chip8 = run_chip8("LD V1, 0x02\nSHR VF, V1");
+    assert_eq!(chip8.state().v[0xF], 0);
+
+    let chip8 = run_chip8("LD V1, 0x80\nSHL VF, V1");
+    assert_eq!(chip8.state().v[0xF], 1);
+}
+
+#[test]
+fn flag_is_read_before_it_is_overwritten() {
+    // VF as VY: the operation uses its old value
+    let chip8 = run_chip8("LD V0, 0x10\nLD VF, 0x20\nADD V0, VF");
+    assert_eq!(chip8.state().v[0], 0x30);
+    assert_eq!(chip8.state().v[0xF], 0);
+
+    let chip8 = run_chip8("LD VF, 0x03\nSHL V0, VF");
+    assert_eq!(chip8.state().v[0], 0x06);
+    assert_eq!(chip8.state().v[0xF], 0);
+}
+
+#[test]
+fn vf_reset_quirk_clears_vf_after_logic_ops() {
+    for op in ["OR", "AND", "XOR"] {
+        let source = format!("LD VF, 1\nLD V0, 0x0F\nLD V1, 0xF0\n{} V0, V1", op);
+        assert_eq!(run_chip8(&source).state().v[0xF], 0, "{}", op);
+        let chip8 = run(&source, Platform::SuperChip);
+        assert_eq!(chip8.state().v[0xF], 1, "{}", op);
+    }
+}
+
+fn load(source: &str) -> Chip8 {
+    let rom = assemble(source).unwrap();
+    let mut chip8 = Chip8::new(Database::builtin());
+    chip8.load_rom(&rom).unwrap();
+    chip8
+}
+
+#[test]
+fn runaway_recursion_overflows_the_stack() {
+    let mut chip8 = load("start: CALL start");
+    for _ in 0..16 {
+        chip8.step(false).unwrap();
+    }
+    let err = chip8.step(false).unwrap_err();
+    assert_eq!(
+        err,
+        StackError::Overflow {
+            address: 0x200,
+            depth: 16
+        }
+    );
+    assert_eq!(chip8.state().pc, 0x200);
+    assert_eq!(chip8.state().stack, vec![0x202; 16]);
+}
+
+#[test]
+fn return_without_call_underflows_the_stack() {
+    let mut chip8 = load("LD V0, 1\nRET");
+    chip8.step(false).unwrap();
+    let err = chip8.step(false).unwrap_err();
+    assert_eq!(err, StackError::Underflow { address: 0x202 });
+}
+
+#[test]
+fn stack_depth_is_configurable() {
+    let mut chip8 = load("start: CALL start");
+    chip8.set_stack(2, false);
+    chip8.step(false).unwrap();
+    chip8.step(false).unwrap();
+    assert!(chip8.step(false).is_err());
+}
+
+#[test]
+fn vip_stack_lives_in_memory() {
+    let mut chip8 = load("CALL sub\nend: JP end\nsub: CALL inner\nRET\ninner: RET");
+    chip8.set_stack(DEFAULT_STACK_DEPTH, true);
+    chip8.step(false).unwrap();
+    chip8.step(false).unwrap();
+    assert_eq!(chip8.state().stack, vec![0x202, 0x206]);
+    assert_eq!(chip8.state().memory[0xECE..0xED0], [0x02, 0x02]);
+    assert_eq!(chip8.state().memory[0xECC..0xECE], [0x02, 0x06]);
+
+    // A ROM can overwrite a return address
+    let mut chip8 = load("CALL sub\nLD V0, 1\nend: JP end\nsub: LD V1, 2\nRET");
+    chip8.set_stack(DEFAULT_STACK_DEPTH, true);
+    chip8.step(false).unwrap();
+    chip8.set(Field::Memory(0xECF), 0x04);
+    chip8.step(false).unwrap();
+    chip8.step(false).unwrap();
+    assert_eq!(chip8.state().pc, 0x204);
+
+    // Even with one no instruction could have pushed
+    for (high, low, pc) in [(0x00, 0x00, 0x000), (0xFF, 0xFE, 0xFFE)] {
+        let mut chip8 = load(
+            "CALL sub
+end: JP end
+sub: RET",
+        );
+        chip8.set_stack(DEFAULT_STACK_DEPTH, true);
+        chip8.step(false).unwrap();
+        chip8.set(Field::Memory(0xECE), high);
+        chip8.set(Field::Memory(0xECF), low);
+        chip8.step(false).unwrap();
+        assert_eq!(chip8.state().pc, pc);
+        chip8.step(false).unwrap();
+    }
+}
+
+#[test]
+fn pc_wraps_around_memory() {
+    // The opcode's second byte is the font's first
+    let mut chip8 = load("");
+    chip8.set(Field::ProgramCounter, 0xFFF);
+    chip8.set(Field::Memory(0xFFF), 0x6A);
+    chip8.step(false).unwrap();
+    assert_eq!(chip8.state().v[0xA], 0xF0);
+    assert_eq!(chip8.state().pc, 0x001);
+
+    let mut chip8 = load("LD V0, 0xFF\nJP V0, 0xF03");
+    chip8.step(false).unwrap();
+    chip8.step(false).unwrap();
+    assert_eq!(chip8.state().pc, 0x002);
+
+    let mut chip8 = load("JP 0x000");
+    chip8.step(false).unwrap();
+    assert_eq!(chip8.state().pc, 0x000);
+}
+
+#[test]
+fn i_wraps_around_memory() {
+    let mut chip8 = load("LD V0, 1\nLD V1, 2\nLD V2, 3\nLD [I], V2");
+    chip8.set(Field::Index, 0xFFE);
+    for _ in 0..4 {
+        chip8.step(false).unwrap();
+    }
+    assert_eq!(chip8.state().memory[0xFFE..], [1, 2]);
+    assert_eq!(chip8.state().memory[0], 3);
+    assert_eq!(chip8.state().index, 0x001);
+
+    let mut chip8 = load("LD V2, [I]");
+    chip8.set(Field::Index, 0xFFE);
+    chip8.set(Field::Memory(0xFFE), 4);
+    chip8.set(Field::Memory(0xFFF), 5);
+    chip8.step(false).unwrap();
+    assert_eq!(chip8.state().v[..3], [4, 5, 0xF0]);
+
+    let mut chip8 = load("LD V0, 123\nLD B, V0");
+    chip8.set(Field::Index, 0xFFE);
+    chip8.step(false).unwrap();
+    chip8.step(false).unwrap();
+    assert_eq!(chip8.state().memory[0xFFE..], [1, 2]);
+    assert_eq!(chip8.state().memory[0], 3);
+}
+
+#[test]
+fn only_the_low_nibble_picks_the_key() {
+    let mut chip8 = load("LD V0, 0x13\nSKP V0\nLD V1, 1\nSKNP V0\nLD V2, 1");
+    let mut keys = [false; 16];
+    keys[3] = true;
+    chip8.set_keys(keys);
+    for _ in 0..4 {
+        chip8.step(false).unwrap();
+    }
+    assert_eq!(chip8.state().v[1], 0);
+    assert_eq!(chip8.state().v[2], 1);
+}
+
+#[test]
+fn soft_resets_keep_memory_outside_the_rom() {
+    // Writes past the ROM, and over its first byte
+    let source = "LD I, 0x300\nLD V0, 0x42\nLD [I], V0\nLD I, 0x200\nLD [I], V0\nend: JP end";
+    let mut chip8 = load(source);
+    let rom = chip8.rom().to_vec();
+    for _ in 0..5 {
+        chip8.step(false).unwrap();
+    }
+    assert_eq!(chip8.state().memory[0x200], 0x42);
+
+    chip8.reset();
+    assert_eq!(chip8.state().memory[0x200..0x200 + rom.len()], rom);
+    assert_eq!(chip8.state().memory[0x300], 0x42);
+
+    chip8.hard_reset();
+    assert_eq!(chip8.state().memory[0x300], 0);
+
+    // Random bytes everywhere but the font and ROM
+    chip8.set_seed(1);
+    chip8.set_memory_fill(MemoryFill::Random);
+    chip8.hard_reset();
+    let memory = chip8.state().memory;
+    assert_eq!(memory[0x200..0x200 + rom.len()], rom);
+    assert_eq!(memory[..5], [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+    assert!(memory[0x300..].iter().filter(|&&byte| byte != 0).count() > 3000);
+}
+
+#[test]
+fn vip_stack_holds_twelve_calls() {
+    let mut chip8 = load("start: CALL start");
+    chip8.set_stack(DEFAULT_STACK_DEPTH, true);
+    for _ in 0..12 {
+        chip8.step(false).unwrap();
+    }
+    assert!(chip8.step(false).is_err());
+}
+
+#[test]
+fn fields_can_be_set_and_read_back() {
+    let mut chip8 = load("end: JP end");
+    for (name, value) in [
+        ("V3", 0x42),
+        ("I", 0x345),
+        ("PC", 0x300),
+        ("DT", 9),
+        ("[0x400]", 0xAB),
+    ] {
+        let field: Field = name.parse().unwrap();
+        chip8.set(field, value);
+        assert_eq!(chip8.state().get(field), value, "{}", name);
+    }
+    assert_eq!(chip8.state().memory[0x400], 0xAB);
+    assert!("VG".parse::<Field>().is_err());
+    assert!("[0x1000]".parse::<Field>().is_err());
+
+    // Fields built by hand are checked too
+    assert_eq!(Field::register(16), None);
+    assert_eq!(Field::memory(0x1000), None);
+    chip8.set(Field::Register(16), 1);
+    chip8.set(Field::Memory(0x1000), 1);
+    assert_eq!(chip8.state().get(Field::Register(16)), 0);
+    assert_eq!(chip8.state().get(Field::Memory(0x1000)), 0);
+}
+
+/// Records every event as text.
+#[derive(Default)]
+struct Events(Vec<String>);
+
+impl Hook for Events {
+    fn before_instruction(&mut self, state: &MachineState, opcode: u16) {
+        self.0
+            .push(format!("before 0x{:03X} {:04X}", state.pc, opcode));
+    }
+
+    fn after_instruction(&mut self, state: &MachineState, address: usize, _opcode: u16) {
+        self.0
+            .push(format!("after 0x{:03X} -> 0x{:03X}", address, state.pc));
+    }
+
+    fn memory_read(&mut self, address: usize, value: u8) {
+        self.0.push(format!("read 0x{:03X} {}", address, value));
+    }
+
+    fn memory_write(&mut self, address: usize, value: u8) {
+        self.0.push(format!("write 0x{:03X} {}", address, value));
+    }
+
+    fn sprite_drawn(&mut self, x: u8, y: u8, n: u8, collision: bool) {
+        self.0.push(format!("draw {} {} {} {}", x, y, n, collision));
+    }
+
+    fn screen_cleared(&mut self) {
+        self.0.push("cls".to_string());
+    }
+
+    fn sound_changed(&mut self, active: bool) {
+        self.0.push(format!("sound {}", active));
+    }
+}
+
+/// Steps through `count` instructions of `source`, returning the events
+/// other than those around each instruction.
+fn events(source: &str, count: usize) -> Vec<String> {
+    let mut chip8 = load(source);
+    let events = Rc::new(RefCell::new(Events::default()));
+    chip8.add_hook(Box::new(Rc::clone(&events)));
+    for _ in 0..count {
+        chip8.step(false).unwrap();
+    }
+    let events = events.take().0;
+    (events.into_iter())
+        .filter(|event| !event.starts_with("before") && !event.starts_with("after"))
+        .collect()
+}
+
+#[test]
+fn hooks_see_each_instruction() {
+    let mut chip8 = load("LD V0, 1\nJP 0x300");
+    let events = Rc::new(RefCell::new(Events::default()));
+    chip8.add_hook(Box::new(Rc::clone(&events)));
+    chip8.step(false).unwrap();
+    chip8.step(false).unwrap();
+    assert_eq!(
+        events.borrow().0,
+        [
+            "before 0x200 6001",
+            "after 0x200 -> 0x202",
+            "before 0x202 1300",
+            "after 0x202 -> 0x300",
+        ]
+    );
+}
+
+#[test]
+fn hooks_see_memory_reads_and_writes() {
+    let events = events("LD I, 0x300\nLD V0, 123\nLD B, V0\nLD V1, [I]", 4);
+    assert_eq!(
+        events,
+        [
+            "write 0x300 1",
+            "write 0x301 2",
+            "write 0x302 3",
+            "read 0x300 1",
+            "read 0x301 2",
+        ]
+    );
+}
+
+#[test]
+fn hooks_see_drawing_and_sound() {
+    let source = "LD V0, 5\nLD F, V0\nDRW V0, V0, 1\nDRW V0, V0, 1\nCLS\nLD ST, V0";
+    let events = events(source, 6);
+    assert_eq!(
+        events,
+        [
+            "read 0x019 240",
+            "draw 5 5 1 false",
+            "read 0x019 240",
+            "draw 5 5 1 true",
+            "cls",
+            "sound true",
+        ]
+    );
+}
+
+#[test]
+fn removed_hooks_stop_firing() {
+    let mut chip8 = load("end: JP end");
+    let events = Rc::new(RefCell::new(Events::default()));
+    let id = chip8.add_hook(Box::new(Rc::clone(&events)));
+    chip8.step(false).unwrap();
+    assert!(chip8.remove_hook(id).is_some());
+    assert!(chip8.remove_hook(id).is_none());
+    chip8.step(false).unwrap();
+    assert_eq!(events.borrow().0.len(), 2);
+}
+
+/// Asks for some writes before the first instruction.
+struct Writer(Vec<(Field, usize)>);
+
+impl Hook for Writer {
+    fn take_writes(&mut self) -> Vec<(Field, usize)> {
+        std::mem::take(&mut self.0)
+    }
+}
+
+#[test]
+fn hook_writes_land_before_the_instruction() {
+    let mut chip8 = load("ADD V0, 1\nADD V1, 1");
+    let writes = vec![(Field::Register(0), 5), (Field::ProgramCounter, 0x202)];
+    chip8.add_hook(Box::new(Writer(writes)));
+    chip8.step(false).unwrap();
+    let state = chip8.state();
+    assert_eq!((state.v[0], state.v[1], state.pc), (5, 1, 0x204));
+}
+
+#[test]
+fn reserved_names_are_not_labels() {
+    for name in ["v0", "VF", "i", "dt", "st", "k", "f", "b"] {
+        assert!(assemble(&format!("{}: JP {}", name, name)).is_err());
+    }
+    assert!(assemble("vx: JP vx").is_ok());
+}
+
+#[test]
+fn disassembly_assembles_back_to_the_rom() {
+    let rom: Vec<u8> = (0..=0xFFFFu16)
+        .flat_map(u16::to_be_bytes)
+        .chain([0xAB])
+        .collect();
+    for platform in [Platform::Chip8, Platform::SuperChip, Platform::XoChip] {
+        let source: Vec<_> = disassemble(&rom, platform)
+            .into_iter()
+            .map(|line| line.text)
+            .collect();
+        assert_eq!(assemble(&source.join("\n")).unwrap(), rom);
+    }
+}
+
+/// The planes lit at a 64x32 screen pixel, checking all four pixels of the
+/// 128x64 screen it covers agree.
+fn pixel(chip8: &Chip8, x: usize, y: usize) -> u8 {
+    let screen = chip8.state().screen;
+    let pixel = screen[y * 2][x * 2];
+    assert!([(1, 0), (0, 1), (1, 1)]
+        .iter()
+        .all(|(dx, dy)| screen[y * 2 + dy][x * 2 + dx] == pixel));
+    pixel
+}
+
+/// Lit 64x32 screen pixels of `plane` in the `width` by `height` box at
+/// the top left of the screen, after drawing.
+fn lit(
+    chip8: &Chip8,
+    plane: u8,
+    (left, top): (usize, usize),
+    (width, height): (usize, usize),
+) -> usize {
+    (top..top + height)
+        .flat_map(|y| (left..left + width).map(move |x| pixel(chip8, x, y)))
+        .filter(|pixel| pixel & plane != 0)
+        .count()
+}
+
+#[test]
+fn sprites_clip_or_wrap_at_the_edges() {
+    let source = "LD I, sprite\nLD V0, 124\nLD V1, 30\nDRW V0, V1, 4\nJP end\n\
+                  sprite: db 0xFF, 0xFF, 0xFF, 0xFF";
+
+    // The position wraps to (60, 30), then the sprite is cut off
+    let chip8 = run(source, Platform::Chip8);
+    assert_eq!(lit(&chip8, 1, (60, 30), (4, 2)), 8);
+    assert_eq!(lit(&chip8, 1, (0, 0), (64, 30)), 0);
+    assert_eq!(lit(&chip8, 1, (0, 30), (60, 2)), 0);
+
+    // Without clipping it carries on at the other edges
+    let chip8 = run(source, Platform::XoChip);
+    assert_eq!(lit(&chip8, 1, (60, 30), (4, 2)), 8);
+    assert_eq!(lit(&chip8, 1, (0, 30), (4, 2)), 8);
+    assert_eq!(lit(&chip8, 1, (60, 0), (4, 2)), 8);
+    assert_eq!(lit(&chip8, 1, (0, 0), (4, 2)), 8);
+    assert_eq!(lit(&chip8, 1, (0, 0), (64, 32)), 32);
+}
+
+#[test]
+fn dxy0_draws_16x16_sprites_outside_chip8() {
+    let source = format!(
+        "LD I, sprite\nLD V0, 0\nDRW V0, V0, 0\nJP end\nsprite: {}",
+        vec!["db 0xFF, 0xFF"; 16].join("\n")
+    );
+    let chip8 = run(&source, Platform::SuperChip);
+    assert_eq!(lit(&chip8, 1, (0, 0), (16, 16)), 256);
+    assert_eq!(lit(&chip8, 1, (0, 0), (64, 32)), 256);
+
+    let chip8 = run(&source, Platform::Chip8);
+    assert_eq!(lit(&chip8, 1, (0, 0), (64, 32)), 0);
+}
+
+#[test]
+fn sprites_draw_to_each_selected_plane() {
+    let source = "LD I, dot\nLD V0, 0\nPLANE 1\nDRW V0, V0, 1\nPLANE 2\nDRW V0, V0, 1\n\
+                  LD V2, VF\nLD I, planes\nPLANE 3\nDRW V0, V0, 1\nJP end\n\
+                  dot: db 0x80\nplanes: db 0x80, 0xC0";
+    let chip8 = run(source, Platform::XoChip);
+    // Plane 2 was empty before the second sprite, so nothing collided
+    assert_eq!(chip8.state().v[2], 0);
+    // The third turned the pixel off on both planes and lit the next one
+    // on plane 2
+    assert_eq!(chip8.state().v[0xF], 1);
+    assert_eq!(pixel(&chip8, 0, 0), 0b00);
+    assert_eq!(pixel(&chip8, 1, 0), 0b10);
+}
+
+#[test]
+fn super_chip_and_xo_chip_instructions_run() {
+    // Scrolling moves the sprite, and a skip steps over all four bytes of
+    // the long load
+    let chip8 = run(
+        "SYS 0x123\nLD I, dot\nLD V0, 0\nDRW V0, V0, 1\nSCD 3\nSCR\nSCR\nSCL\n\
+         SE V0, 0\nLD I, LONG\ndw 0x0ABC\nLD I, LONG\ndw 0x0DEF\nJP end\ndot: db 0x80",
+        Platform::XoChip,
+    );
+    assert_eq!(lit(&chip8, 1, (0, 0), (64, 32)), 1);
+    assert_eq!(pixel(&chip8, 4, 3), 1);
+    assert_eq!(chip8.state().index, 0xDEF);
+
+    // Registers go to and from memory without moving I, and to and from
+    // the user flags
+    let chip8 = run(
+        "LD I, 0x300\nLD V1, 1\nLD V2, 2\nLD V3, 3\nSAVE V3, V1\nLOAD V4, V6\n\
+         LD R, V3\nLD V0, 9\nLD V1, 9\nLD V1, R",
+        Platform::XoChip,
+    );
+    assert_eq!(chip8.state().memory[0x300..0x303], [3, 2, 1]);
+    assert_eq!(chip8.state().v[4..7], [3, 2, 1]);
+    assert_eq!(chip8.state().v[..2], [0, 1]);
+    assert_eq!(chip8.state().index, 0x300);
+
+    // The big font follows the small one
+    let chip8 = run("LD V0, 0x1A\nLD HF, V0", Platform::SuperChip);
+    assert_eq!(chip8.state().index, 80 + 10 * 0xA);
+
+    // On CHIP-8 these are calls to machine code and unknown opcodes, and
+    // do nothing
+    let chip8 = run(
+        "LD V0, 1\nHIGH\nSCR\nLD I, 0x300\nLD I, LONG\ndw 0x6005\nSAVE V0, V1",
+        Platform::Chip8,
+    );
+    assert_eq!(chip8.state().v[0], 5);
+    assert_eq!(chip8.state().index, 0x300);
+    assert_eq!(chip8.state().memory[0x300], 0);
+}
+
+#[test]
+fn hires_draws_single_pixels() {
+    let source = "HIGH\nLD I, sprite\nLD V0, 125\nLD V1, 63\nDRW V0, V1, 1\nJP end\n\
+                  sprite: db 0xF0";
+    let chip8 = run(source, Platform::SuperChip);
+    let screen = chip8.state().screen;
+    assert_eq!(screen[63][125..], [1, 1, 1]);
+    assert_eq!(
+        screen.iter().flatten().filter(|&&pixel| pixel != 0).count(),
+        3
+    );
+
+    // Switching back clears the screen
+    let chip8 = run(
+        &source.replace("JP end", "LOW\nJP end"),
+        Platform::SuperChip,
+    );
+    assert!(chip8
+        .state()
+        .screen
+        .iter()
+        .flatten()
+        .all(|&pixel| pixel == 0));
+}
+
+#[test]
+fn xo_chip_audio_patterns_reach_the_sound_frame() {
+    let mut chip8 = load(
+        "LD I, pattern\nAUDIO\nLD V0, 112\nLD PITCH, V0\nend: JP end\n\
+         pattern: db 0xFF, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0",
+    );
+    chip8.set_platform(Platform::XoChip);
+    chip8.run_frame(false).unwrap();
+    let pattern = chip8.sound_frame().pattern().unwrap();
+    // 48 steps of pitch double the rate
+    assert_eq!(pattern.loops_per_second(), 8000.0 / 128.0);
+    assert_eq!(pattern.sample(0.0), 1.0);
+    assert_eq!(pattern.sample(0.5), -1.0);
+
+    chip8.set_platform(Platform::Chip8);
+    chip8.run_frame(false).unwrap();
+    assert_eq!(chip8.sound_frame().pattern(), None);
+}
+
+#[test]
+fn vip_timing_charges_each_opcode_its_cycles() {
+    let chip8 = run(
+        "LD V0, 123\nLD V1, 8\nLD V2, 3\nLD I, 0x2F0",
+        Platform::SuperChip,
+    );
+    let cost = |opcode| {
+        let instruction = Instruction::from_opcode(opcode, Platform::SuperChip);
+        Timing::Vip.cost(&chip8, &instruction, 0)
+    };
+    // Every instruction also pays 40 cycles to be fetched and decoded
+    assert_eq!(cost(0x6005), 40 + 6);
+    assert_eq!(cost(0x8014), 40 + 44);
+    // Skips taken take 4 cycles more
+    assert_eq!(cost(0x307B), 40 + 14);
+    assert_eq!(cost(0x307C), 40 + 10);
+    // BCD pays for each digit's value
+    assert_eq!(cost(0xF033), 40 + 80 + 16 * 6);
+    // Sprite rows not aligned to a byte take two writes
+    assert_eq!(cost(0xD125), 40 + 68 + 5 * 34);
+    assert_eq!(cost(0xD215), 40 + 68 + 5 * 54);
+    // Adding to I past the end of a page carries
+    assert_eq!(cost(0xF01E), 40 + 18);
+    assert_eq!(cost(0xF21E), 40 + 16);
+    assert_eq!(cost(0xF265), 40 + 14 + 14 * 3);
+
+    // A fixed timing counts instructions
+    let instruction = Instruction::from_opcode(0xF033, Platform::SuperChip);
+    assert_eq!(Timing::Fixed.cost(&chip8, &instruction, 0), 1);
+}
+
+#[test]
+fn vip_timing_fits_a_frame_of_cycles() {
+    // The VIP has 3668 - 1832 cycles a frame after the display interrupt
+    assert_eq!(Timing::Vip.frame_budget(15), 1836);
+    assert_eq!(Timing::Fixed.frame_budget(15), 15);
+
+    // 40 loads of 46 cycles each run before the frame is over; the last
+    // one runs past its end
+    let source = "LD V0, 1\n".repeat(200);
+    let mut chip8 = load(&source);
+    chip8.set_timing(Timing::Vip);
+    chip8.run_frame(false).unwrap();
+    assert_eq!(chip8.state().pc, 0x200 + 2 * 40);
+    // The 4 cycles it ran over count against the next frame, which ends
+    // on its 40th load with 8 over
+    chip8.run_frame(false).unwrap();
+    assert_eq!(chip8.state().pc, 0x200 + 2 * 80);
+    chip8.run_frame(false).unwrap();
+    assert_eq!(chip8.state().pc, 0x200 + 2 * 120);
+}
+
+#[test]
+fn display_wait_ends_the_frame_at_a_draw() {
+    let source = "LD V0, 1\nDRW V0, V0, 1\nLD V1, 1\nLD V2, 2\nend: JP end";
+    let mut chip8 = load(source);
+    chip8.run_frame(false).unwrap();
+    // The draw used up what was left of the frame
+    assert_eq!(chip8.state().pc, 0x204);
+    assert_eq!(chip8.state().v[1], 0);
+    chip8.run_frame(false).unwrap();
+    assert_eq!(chip8.state().v[1..3], [1, 2]);
+
+    // Without the quirk the frame goes on after the draw
+    let mut chip8 = load(source);
+    chip8.set_platform(Platform::SuperChip);
+    chip8.run_frame(false).unwrap();
+    assert_eq!(chip8.state().v[1..3], [1, 2]);
+
+    // With the VIP timing too
+    let mut chip8 = load(source);
+    chip8.set_timing(Timing::Vip);
+    chip8.run_frame(false).unwrap();
+    assert_eq!(chip8.state().pc, 0x204);
+}
+
+/// A full volume square wave at 750 Hz, 64 samples a cycle at 48 kHz.
+fn square_tone() -> Tone {
+    let beeper = Beeper {
+        waveform: Waveform::Square,
+        frequency: 750.0,
+        volume: 1.0,
+    };
+    Tone::new(48000, beeper)
+}
+
+#[test]
+fn tones_repeat_at_their_frequency() {
+    let mut tone = square_tone();
+    // Past the 5 ms fade in
+    let samples: Vec<f32> = (0..1000).map(|_| tone.next_sample(true)).collect();
+    let cycle = &samples[320..384];
+    assert!(cycle[..32].iter().all(|&sample| sample == 0.2));
+    assert!(cycle[32..].iter().all(|&sample| sample == -0.2));
+    assert_eq!(samples[384..448], *cycle);
+}
+
+#[test]
+fn tones_fade_in_and_out_from_silence() {
+    let mut tone = square_tone();
+    let samples: Vec<f32> = (0..300).map(|_| tone.next_sample(true)).collect();
+    // 240 samples to reach full volume
+    assert!(samples[0].abs() < 0.001);
+    assert!(samples
+        .windows(2)
+        .take(239)
+        .all(|pair| pair[1].abs() > pair[0].abs()));
+    assert!((samples[239].abs() - 0.2).abs() < 1e-6);
+
+    let samples: Vec<f32> = (0..300).map(|_| tone.next_sample(false)).collect();
+    assert!(samples[0].abs() > 0.199);
+    // Give or take a sample of rounding
+    assert!(samples[239].abs() < 1e-6);
+    assert!(samples[241..].iter().all(|&sample| sample == 0.0));
+}
+
+#[test]
+fn beeps_shorter_than_a_frame_sound_for_part_of_it() {
+    // The sound timer starts 6 instructions into a frame of 10, and runs
+    // out when the frame ends
+    let mut chip8 = load(&format!(
+        "{}LD V0, 1\nLD ST, V0\nend: JP end",
+        "LD V1, 0\n".repeat(4)
+    ));
+    chip8.set_speed(10);
+    chip8.run_frame(false).unwrap();
+    let mut tone = square_tone();
+    let mut samples = [0.0; 800];
+    tone.write_frame(chip8.sound_frame(), &mut samples);
+    assert!(samples[..480].iter().all(|&sample| sample == 0.0));
+    assert!(samples[480..].iter().all(|&sample| sample != 0.0));
+}
+
+#[test]
+fn whole_frames_are_dropped_when_the_device_falls_behind() {
+    let mut sound = SoundFrame::default();
+    sound.begin(true);
+    let (mut producer, mut consumer) = RingBuffer::new(10);
+    let mut tone = square_tone();
+    let mut queue = |tone: &mut Tone| queue_frame(&mut producer, tone, &sound, &mut [0.0; 4]);
+    // The third frame doesn't fit, and isn't generated either
+    for _ in 0..3 {
+        queue(&mut tone);
+    }
+    let mut expected = square_tone();
+    let frames: Vec<f32> = (0..12).map(|_| expected.next_sample(true)).collect();
+    let queued: Vec<f32> = (0..6).map(|_| consumer.pop().unwrap()).collect();
+    assert_eq!(queued, frames[..6]);
+
+    // The next one wraps around the end of the buffer and carries on from
+    // the second
+    queue(&mut tone);
+    let queued: Vec<f32> = std::iter::from_fn(|| consumer.pop().ok()).collect();
+    assert_eq!(queued, frames[6..12]);
+}
+
+/// The type and data of each chunk of a PNG.
+fn png_chunks(png: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut chunks = vec![];
+    let mut rest = &png[8..];
+    while !rest.is_empty() {
+        let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        chunks.push((&rest[4..8], &rest[8..8 + length]));
+        rest = &rest[12 + length..];
+    }
+    chunks
+}
+
+#[test]
+fn apng_recordings_get_their_frame_count() {
+    let mut file = Cursor::new(vec![]);
+    let mut writer = start_apng(&mut file).unwrap();
+    let frame = scale(&[0; SCREEN_WIDTH * SCREEN_HEIGHT * 4]);
+    for _ in 0..3 {
+        writer.write_image_data(&frame).unwrap();
+    }
+    writer.finish().unwrap();
+    patch_frame_count(&mut file, 3).unwrap();
+
+    let png = file.into_inner();
+    let chunks = png_chunks(&png);
+    let (_, actl) = chunks.iter().find(|(kind, _)| kind == b"acTL").unwrap();
+    assert_eq!(actl[..4], 3u32.to_be_bytes());
+    // Frame controls and the data of every frame after the first share a
+    // sequence
+    let sequence: Vec<u32> = (chunks.iter())
+        .filter(|(kind, _)| kind == b"fcTL" || kind == b"fdAT")
+        .map(|(_, data)| u32::from_be_bytes(data[..4].try_into().unwrap()))
+        .collect();
+    assert_eq!(sequence, (0..5).collect::<Vec<_>>());
+    let frame_controls = chunks.iter().filter(|(kind, _)| kind == b"fcTL");
+    assert_eq!(frame_controls.count(), 3);
+
+    // And the patched CRC checks out
+    let reader = png::Decoder::new(Cursor::new(&png)).read_info().unwrap();
+    assert_eq!(reader.info().animation_control().unwrap().num_frames, 3);
+}
+
+#[test]
+fn av_recordings_keep_audio_and_video_in_step() {
+    let dir = std::env::temp_dir().join(format!("chip8-rs-av-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    // The extensions go after any dots already in the name
+    let base = dir.join("clip.v1");
+    let mut recorder = AvRecorder::new(&base, Beeper::default()).unwrap();
+    let mut sound = SoundFrame::default();
+    sound.begin(true);
+    let frame = [0; SCREEN_WIDTH * SCREEN_HEIGHT * 4];
+    for _ in 0..3 {
+        recorder.push(&frame, &sound).unwrap();
+    }
+    recorder.finish().unwrap();
+
+    let y4m = fs::read(dir.join("clip.v1.y4m")).unwrap();
+    let header = b"YUV4MPEG2 W512 H256 F60:1 Ip A1:1 C444\n";
+    assert_eq!(y4m[..header.len()], *header);
+    // Each frame is a full plane each of Y, U and V
+    let plane = 512 * 256;
+    let frames = &y4m[header.len()..];
+    assert_eq!(frames.len(), 3 * (6 + 3 * plane));
+    assert_eq!(frames[..6], *b"FRAME\n");
+    let first = &frames[6..6 + 3 * plane];
+    // Black is 16 in limited range luma, and neutral in chroma
+    assert!(first[..plane].iter().all(|&y| y == 16));
+    assert!(first[plane..].iter().all(|&uv| uv == 128));
+
+    // 735 samples at 44.1 kHz for each 60 Hz frame
+    let wav = hound::WavReader::open(dir.join("clip.v1.wav")).unwrap();
+    assert_eq!(wav.duration(), 3 * 735);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+const MAZE: &[u8] = include_bytes!("../../roms/maze.ch8");
+const MAZE_HASH: &str = "b9272ae1acdaaa79ab649f6b48b72088ca2b1d74";
+
+#[test]
+fn local_database_entries_replace_builtin_ones() {
+    let builtin = Database::builtin();
+    assert_eq!(
+        builtin.lookup(MAZE).unwrap().title.as_deref(),
+        Some("Maze (David Winter)")
+    );
+
+    let path = std::env::temp_dir().join(format!("chip8-rs-db-{}.toml", std::process::id()));
+    fs::write(&path, format!("[{}]\ntitle = \"My Maze\"\n", MAZE_HASH)).unwrap();
+    let database = Database::with_overrides(path.to_str().unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    let config = database.lookup(MAZE).unwrap();
+    assert_eq!(config.title.as_deref(), Some("My Maze"));
+    // The whole entry, not field by field
+    assert_eq!(config.palette, None);
+    // Other entries are still there
+    let pong = include_bytes!("../../roms/pong_1_player.ch8");
+    assert!(database.lookup(pong).is_some());
+}
+
+#[test]
+fn database_hashes_match_in_any_case() {
+    let database = Database::parse(&format!(
+        "[{}]\ntitle = \"Maze\"\n",
+        MAZE_HASH.to_uppercase()
+    ))
+    .unwrap();
+    assert_eq!(
+        database.lookup(MAZE).unwrap().title.as_deref(),
+        Some("Maze")
+    );
+}
+
+#[test]
+fn unknown_database_fields_are_rejected() {
+    for source in [
+        "[abc]\ntitel = \"Maze\"",
+        "[abc.quirks]\nwrapping = true",
+        "[abc.colors]\nborder = \"#000000\"",
+    ] {
+        let err = Database::parse(source).err().expect(source);
+        assert!(err.to_string().contains("unknown field"), "{}", err);
+    }
+}
+
+#[test]
+fn command_line_beats_database_beats_platform() {
+    let database = || {
+        let source = format!(
+            "[{}]\nplatform = \"superchip\"\nspeed = 20\n[{0}.quirks]\njumping = false\nclipping = false\n",
+            MAZE_HASH
+        );
+        Database::parse(&source).unwrap()
+    };
+    let mut chip8 = Chip8::new(database());
+    chip8.load_rom(MAZE).unwrap();
+    // The database's quirk over the platform's, and the platform's where
+    // the database has none
+    assert_eq!(chip8.platform(), Platform::SuperChip);
+    assert!(!chip8.quirks.jumping);
+    assert!(!chip8.quirks.clipping);
+    assert!(chip8.quirks.shifting);
+    assert_eq!(chip8.speed, 20);
+
+    chip8.set_quirk_overrides(QuirkOverrides {
+        jumping: Some(true),
+        ..Default::default()
+    });
+    assert!(chip8.quirks.jumping);
+    chip8.set_speed(7);
+    assert_eq!(chip8.speed, 7);
+
+    // A platform from the command line brings its own quirks and speed,
+    // with the database's quirks still applied on top
+    let mut chip8 = Chip8::new(database());
+    chip8.set_platform(Platform::Chip8);
+    chip8.load_rom(MAZE).unwrap();
+    assert_eq!(chip8.platform(), Platform::Chip8);
+    assert!(!chip8.quirks.shifting);
+    assert!(!chip8.quirks.clipping);
+    assert_eq!(chip8.speed, 10);
+}
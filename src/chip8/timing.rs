@@ -0,0 +1,110 @@
+use super::{instruction::Instruction, Chip8};
+use std::str::FromStr;
+
+/// Machine cycles (8 clock cycles each) of the COSMAC VIP's 1.76 MHz
+/// CDP1802 in a 60 Hz frame
+const VIP_FRAME_CYCLES: u32 = 3668;
+/// Cycles of each frame spent in the display interrupt routine while the
+/// screen is being drawn
+const VIP_DISPLAY_CYCLES: u32 = 1832;
+/// Cycles the interpreter spends fetching and decoding every instruction
+const VIP_FETCH_CYCLES: u32 = 40;
+
+/// How long instructions take.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Timing {
+    /// A fixed number of instructions per frame, set by the speed
+    #[default]
+    Fixed,
+    /// What each instruction took on the original COSMAC VIP interpreter
+    Vip,
+}
+
+impl Timing {
+    /// Cost of the instructions that fit in a frame: a count with fixed
+    /// timing, or VIP machine cycles.
+    pub(super) fn frame_budget(&self, speed: u32) -> u32 {
+        match self {
+            Timing::Fixed => speed,
+            Timing::Vip => VIP_FRAME_CYCLES - VIP_DISPLAY_CYCLES,
+        }
+    }
+
+    /// Cost of `instruction`, about to be executed by `chip8`, in the units
+    /// of `frame_budget`. `elapsed` is the cost already spent this frame.
+    pub(super) fn cost(&self, chip8: &Chip8, instruction: &Instruction, elapsed: u32) -> u32 {
+        // With the display wait quirk, DRW waits for the vertical blank,
+        // stalling until the end of the frame
+        let wait = match instruction {
+            Instruction::DRWVxVyNibble(..) if chip8.quirks.display_wait => {
+                self.frame_budget(chip8.speed).saturating_sub(elapsed)
+            }
+            _ => 0,
+        };
+        match self {
+            Timing::Fixed => wait.max(1),
+            // The VIP waits before drawing, so drawing counts against the
+            // next frame
+            Timing::Vip => VIP_FETCH_CYCLES + vip_cycles(chip8, instruction) + wait,
+        }
+    }
+}
+
+impl FromStr for Timing {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "fixed" => Ok(Timing::Fixed),
+            "vip" => Ok(Timing::Vip),
+            _ => Err(format!("unknown timing `{}` (expected fixed or vip)", name)),
+        }
+    }
+}
+
+/// Approximate machine cycles the VIP interpreter spends executing
+/// `instruction`, after fetching it and not counting the wait before `DRW`.
+/// Taken from Laurence Scotford's analysis of the interpreter.
+fn vip_cycles(chip8: &Chip8, instruction: &Instruction) -> u32 {
+    use Instruction::*;
+
+    let v = &chip8.v;
+    let skip = |taken: bool, cycles: u32| if taken { cycles + 4 } else { cycles };
+    let crosses_page = |base: usize, offset: usize| (base & 0xFF) + offset > 0xFF;
+    match *instruction {
+        CLS => 24,
+        RET => 10,
+        SYS(_) => 12,
+        JP(_) => 12,
+        CALL(_) => 26,
+        SEVxByte(x, kk) => skip(v[x] == kk, 10),
+        SNEVxByte(x, kk) => skip(v[x] != kk, 10),
+        SEVxVy(x, y) => skip(v[x] == v[y], 14),
+        LDVxByte(..) => 6,
+        ADDVxByte(..) => 10,
+        LDVxVy(..) | ORVxVy(..) | ANDVxVy(..) | XORVxVy(..) | ADDVxVy(..) | SUBVxVy(..)
+        | SHRVxVy(..) | SUBNVxVy(..) | SHLVxVy(..) => 44,
+        SNEVxVy(x, y) => skip(v[x] != v[y], 14),
+        LDI(_) => 12,
+        JP0(nnn) => 22 + 2 * crosses_page(nnn, v[0] as usize) as u32,
+        RNDVxByte(..) => 36,
+        DRWVxVyNibble(x, _, n) => {
+            // Rows not aligned to a byte of display memory take two writes
+            let row_cycles = if v[x] & 7 == 0 { 34 } else { 54 };
+            68 + n as u32 * row_cycles
+        }
+        SKPVx(x) => skip(chip8.keys[v[x] as usize & 0xF], 14),
+        SKNPVx(x) => skip(!chip8.keys[v[x] as usize & 0xF], 14),
+        LDVxDT(_) | LDVxK(_) | LDDTVx(_) | LDSTVx(_) => 10,
+        ADDIVx(x) => 16 + 2 * crosses_page(chip8.index, v[x] as usize) as u32,
+        LDFVx(_) => 20,
+        LDBVx(x) => {
+            let digits = v[x] / 100 + v[x] / 10 % 10 + v[x] % 10;
+            80 + 16 * digits as u32
+        }
+        LDIVx(x) | LDVxMem(x) => 14 + 14 * (x as u32 + 1),
+        // Not on the VIP
+        PLANE(_) | SCD(_) | SCU(_) | SCR | SCL | EXIT | LOW | HIGH | LDHFVx(_) | LDRVx(_)
+        | LDVxR(_) | SAVEVxVy(..) | LOADVxVy(..) | LDILong | AUDIO | PITCHVx(_) | Unknown => 0,
+    }
+}
@@ -0,0 +1,281 @@
+use crate::chip8::{
+    AudioOutput, DisplayFilter, MemoryFill, Palette, Platform, QuirkOverrides, RecordingFormat,
+    Timing, Waveform, DEFAULT_STACK_DEPTH,
+};
+use crate::headless::Expectation;
+use crate::shader::Effects;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use std::{ffi::OsString, path::PathBuf};
+
+/// CHIP-8 interpreter, disassembler and assembler
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+impl Cli {
+    /// Parses the command line, treating `chip8-rs ROM ...` as
+    /// `chip8-rs run ROM ...`.
+    pub fn parse_with_default_command() -> Self {
+        let mut args: Vec<OsString> = std::env::args_os().collect();
+        let command = Self::command();
+        let is_known = |arg: &str| {
+            matches!(arg, "help" | "-h" | "--help" | "-V" | "--version")
+                || command.find_subcommand(arg).is_some()
+        };
+        if args
+            .get(1)
+            .is_some_and(|arg| !is_known(&arg.to_string_lossy()))
+        {
+            args.insert(1, "run".into());
+        }
+        Self::parse_from(args)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run a ROM (the default)
+    Run(Box<RunArgs>),
+    /// Print the disassembly of a ROM
+    Disasm(DisasmArgs),
+    /// Assemble a source file into a ROM
+    Asm(AsmArgs),
+    /// Print the size, hash, platform and opcode statistics of a ROM
+    Info(InfoArgs),
+    /// Run a ROM without a window and check the machine state afterwards
+    Test(TestArgs),
+}
+
+/// Options shared by everything that runs a ROM.
+#[derive(Args, Debug)]
+pub struct MachineArgs {
+    /// Enable debug mode
+    #[arg(short, long, default_value_t = false)]
+    pub debug: bool,
+
+    /// ROM database file whose entries override the built-in ones
+    #[arg(long)]
+    pub database: Option<String>,
+
+    /// Platform to emulate: chip8, superchip or xochip (default: from the
+    /// database, or chip8)
+    #[arg(long)]
+    pub platform: Option<Platform>,
+
+    /// Quirks to turn on or off, e.g. jumping,vf_reset=off
+    #[arg(long)]
+    pub quirks: Option<QuirkOverrides>,
+
+    /// Instructions executed per frame (default: from the database, or the
+    /// platform's)
+    #[arg(long)]
+    pub speed: Option<u32>,
+
+    /// How long instructions take: fixed (--speed instructions per frame)
+    /// or vip (as on the COSMAC VIP, ignoring --speed)
+    #[arg(long, default_value = "fixed")]
+    pub timing: Timing,
+
+    /// Nested subroutine calls allowed before a stack overflow
+    #[arg(long, default_value_t = DEFAULT_STACK_DEPTH)]
+    pub stack_depth: usize,
+
+    /// Keep the stack in memory below 0xED0 like the COSMAC VIP, where ROMs
+    /// can overwrite it and there is room for 12 calls
+    #[arg(long, default_value_t = false)]
+    pub vip_stack: bool,
+
+    /// Seed for the random number generator, for repeatable runs
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// What memory outside the font and ROM holds at start and after a hard
+    /// reset: zero or random
+    #[arg(long, default_value = "zero")]
+    pub memory_fill: MemoryFill,
+}
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// Path to the ROM file, a directory or zip archive of ROMs to pick
+    /// from, or - for standard input
+    pub rom: String,
+
+    #[command(flatten)]
+    pub machine: MachineArgs,
+
+    /// Color palette: default, green, amber, lcd or contrast (F2 cycles at
+    /// runtime), instead of the one the ROM database picks
+    #[arg(short, long)]
+    pub palette: Option<Palette>,
+
+    /// Anti-flicker filter: none, phosphor or blend (F3 cycles at runtime)
+    #[arg(short, long, default_value = "none")]
+    pub filter: DisplayFilter,
+
+    /// Post-processing effects: a comma-separated list of scanlines,
+    /// curvature, bloom and grid, or crt for the first three
+    #[arg(long, default_value = "none")]
+    pub shader: Effects,
+
+    /// Render with a software adapter (e.g. llvmpipe) instead of the GPU
+    #[arg(long, default_value_t = false)]
+    pub software: bool,
+
+    /// Read debugger commands (reset, break, step, continue, regs, ...)
+    /// from standard input while running
+    #[arg(long, default_value_t = false)]
+    pub debugger: bool,
+
+    /// Reload the ROM whenever the file changes, e.g. after reassembling it
+    #[arg(long, default_value_t = false)]
+    pub watch: bool,
+
+    /// With --watch, keep the key mapping across reloads instead of looking
+    /// it up again for the changed ROM
+    #[arg(long, default_value_t = false, requires = "watch")]
+    pub keep_keys: bool,
+
+    /// With --watch, keep the debugger's breakpoints across reloads instead
+    /// of clearing them, for ROMs whose code hasn't moved
+    #[arg(long, default_value_t = false, requires = "watch")]
+    pub keep_breakpoints: bool,
+
+    /// Initial window size, in window pixels per CHIP-8 pixel (at least 4)
+    #[arg(long, default_value_t = 12.0)]
+    pub scale: f64,
+
+    /// Where the beeper goes: device, none or file. Falls back to none
+    /// when there is no usable output device
+    #[arg(long, default_value = "device")]
+    pub audio: AudioOutput,
+
+    /// WAV file written with --audio file
+    #[arg(long, default_value = "audio.wav")]
+    pub audio_file: PathBuf,
+
+    /// Beeper waveform: sine, square, triangle or noise
+    #[arg(long, default_value = "sine")]
+    pub waveform: Waveform,
+
+    /// Beeper pitch in Hz
+    #[arg(long, default_value_t = 440.0)]
+    pub frequency: f32,
+
+    /// Beeper volume from 0 to 1 (- and + change it at runtime, M mutes)
+    #[arg(long, default_value_t = 0.25)]
+    pub volume: f32,
+
+    /// Directory for screenshots (F12) and recordings (F10)
+    #[arg(long, default_value = ".")]
+    pub capture_dir: PathBuf,
+
+    /// Format of recordings: gif or apng
+    #[arg(long, default_value = "gif")]
+    pub record_format: RecordingFormat,
+
+    /// Record the display from the first frame to this file, saved on exit
+    #[arg(long)]
+    pub record: Option<PathBuf>,
+
+    /// Save a screenshot to this file on exit
+    #[arg(long)]
+    pub screenshot: Option<PathBuf>,
+
+    /// Record audio and video to <BASE>.wav and <BASE>.y4m, saved on exit
+    #[arg(long, value_name = "BASE")]
+    pub record_av: Option<PathBuf>,
+
+    /// File of cheat codes per ROM, applied before every frame; the
+    /// debugger's cheat command saves them here
+    #[arg(long, default_value = "cheats.toml")]
+    pub cheats: PathBuf,
+
+    /// File of achievements per ROM, checked every frame
+    #[arg(long, default_value = "achievements.toml")]
+    pub achievements: PathBuf,
+
+    /// File the unlocked achievements are saved to
+    #[arg(long, default_value = "unlocked.toml")]
+    pub unlocked: PathBuf,
+
+    /// Profile the ROM and save a report of how often each instruction ran,
+    /// its hottest loops and code that never ran to this file on exit (- for
+    /// standard output)
+    #[arg(long, value_name = "FILE")]
+    pub profile: Option<PathBuf>,
+
+    /// Save a PNG heatmap of how often each byte of memory was executed,
+    /// read or written to this file on exit
+    #[arg(long, value_name = "FILE")]
+    pub heatmap: Option<PathBuf>,
+
+    /// Run a Rhai script alongside the ROM, calling its on_frame and
+    /// on_instruction functions
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<PathBuf>,
+
+    /// Run without a window or audio device, as fast as possible
+    #[arg(long, default_value_t = false)]
+    pub headless: bool,
+
+    /// Number of frames to run in headless mode
+    #[arg(long, default_value_t = 600)]
+    pub frames: u32,
+}
+
+#[derive(Args, Debug)]
+pub struct DisasmArgs {
+    /// Path to the ROM file
+    pub rom: PathBuf,
+
+    /// Write the listing to this file instead of standard output
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct AsmArgs {
+    /// Path to the source file
+    pub source: PathBuf,
+
+    /// Path of the ROM to write
+    #[arg(short, long)]
+    pub output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct InfoArgs {
+    /// Path to the ROM file
+    pub rom: PathBuf,
+
+    /// ROM database file whose entries override the built-in ones
+    #[arg(long)]
+    pub database: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct TestArgs {
+    /// Path to the ROM file, or - for standard input
+    pub rom: String,
+
+    #[command(flatten)]
+    pub machine: MachineArgs,
+
+    /// Number of frames to run before checking
+    #[arg(long, default_value_t = 600)]
+    pub frames: u32,
+
+    /// Expected value after the run, e.g. V3=0x10, I=0x300, PC=0x22A, DT=0
+    /// or [0x300]=5 for memory (repeatable)
+    #[arg(long)]
+    pub expect: Vec<Expectation>,
+
+    /// Screenshot (as saved by F12 or --screenshot with the default
+    /// palette) the screen must match after the run
+    #[arg(long)]
+    pub expect_screen: Option<PathBuf>,
+}
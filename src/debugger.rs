@@ -0,0 +1,246 @@
+use crate::cheats::{Cheat, SearchFilter};
+use crate::chip8::{disassemble, parse_number, Chip8, Field, Hook, MachineState};
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead},
+    str::FromStr,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+const HELP: &str = "\
+reset          restart the ROM
+hard-reset     restart the ROM with memory refilled
+break [ADDR]   toggle a breakpoint, or list them
+watch [ADDR]   toggle pausing when ADDR is written, or list watched addresses
+step [N]       pause and execute N instructions (default 1)
+continue       resume running
+pause          pause
+regs           print the registers
+set FIELD VAL  set V0-VF, I, PC, DT, ST or [ADDR] to a value
+mem ADDR [N]   print N bytes of memory (default 16)
+search [HOW]   narrow down addresses: new, eq VAL, changed, same, down or up
+cheat [CODE]   hold a byte at a value with ADDR=VAL and save it, or list cheats
+uncheat ADDR   remove the cheat on an address
+help           print this list";
+
+/// A command typed into the debugger console.
+#[derive(Clone, Copy, Debug)]
+pub enum DebugCommand {
+    Reset,
+    HardReset,
+    Break(Option<usize>),
+    Watch(Option<usize>),
+    Step(u32),
+    Continue,
+    Pause,
+    Regs,
+    Set(Field, usize),
+    Memory(usize, usize),
+    Search(SearchFilter),
+    Cheat(Option<Cheat>),
+    Uncheat(usize),
+    Help,
+}
+
+impl FromStr for DebugCommand {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default().to_ascii_lowercase();
+        let argument = words.next();
+        let second = words.next();
+        let number = |default| match argument {
+            Some(text) => parse_number(text).ok_or_else(|| format!("invalid number `{}`", text)),
+            None => Ok(default),
+        };
+
+        let command = match name.as_str() {
+            "reset" => DebugCommand::Reset,
+            "hard-reset" => DebugCommand::HardReset,
+            "break" | "b" => match argument {
+                Some(_) => DebugCommand::Break(Some(number(0)?)),
+                None => DebugCommand::Break(None),
+            },
+            "watch" | "w" => match argument {
+                Some(_) => DebugCommand::Watch(Some(number(0)?)),
+                None => DebugCommand::Watch(None),
+            },
+            "step" | "s" => DebugCommand::Step(number(1)? as u32),
+            "continue" | "c" => DebugCommand::Continue,
+            "pause" => DebugCommand::Pause,
+            "regs" | "r" => DebugCommand::Regs,
+            "set" => {
+                let field: Field = argument.ok_or("expected a field")?.parse()?;
+                let value = second.ok_or("expected a value")?;
+                let value = parse_number(value)
+                    .filter(|&value| value <= field.max())
+                    .ok_or_else(|| format!("invalid value `{}` for {}", value, field))?;
+                DebugCommand::Set(field, value)
+            }
+            "search" => {
+                let rest = line.trim().split_once(char::is_whitespace);
+                DebugCommand::Search(rest.map(|(_, rest)| rest).unwrap_or("").parse()?)
+            }
+            "cheat" => match argument {
+                Some(code) => DebugCommand::Cheat(Some(code.parse()?)),
+                None => DebugCommand::Cheat(None),
+            },
+            "uncheat" => {
+                argument.ok_or("expected an address")?;
+                DebugCommand::Uncheat(number(0)?)
+            }
+            "mem" | "m" => {
+                let length = match second {
+                    Some(text) => {
+                        parse_number(text).ok_or_else(|| format!("invalid number `{}`", text))?
+                    }
+                    None => 16,
+                };
+                DebugCommand::Memory(number(0)?, length)
+            }
+            "help" | "?" => DebugCommand::Help,
+            _ => return Err(format!("unknown command `{}` (try help)", name)),
+        };
+        Ok(command)
+    }
+}
+
+/// Reads debugger commands from standard input on a background thread, so
+/// they can be typed in the terminal while the window is open.
+pub struct Console {
+    lines: Receiver<String>,
+}
+
+impl Console {
+    pub fn spawn() -> Self {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        println!("[Debugger] Reading commands from standard input, type help for a list");
+        Self { lines }
+    }
+
+    /// The commands typed since the last call. Invalid ones are reported
+    /// and skipped.
+    pub fn poll(&self) -> Vec<DebugCommand> {
+        self.lines
+            .try_iter()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match line.parse() {
+                Ok(command) => Some(command),
+                Err(err) => {
+                    eprintln!("[Debugger] {}", err);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// A write to a watched address.
+pub struct WatchHit {
+    /// Address of the instruction that wrote it
+    pub pc: usize,
+    pub address: usize,
+    pub value: u8,
+}
+
+/// Hook noting writes to watched addresses, for the debugger to pause on.
+#[derive(Default)]
+pub struct Watchpoints {
+    addresses: BTreeSet<usize>,
+    pc: usize,
+    hits: Vec<WatchHit>,
+}
+
+impl Watchpoints {
+    /// Watches `address`, or stops watching it. Returns whether it is now
+    /// watched.
+    pub fn toggle(&mut self, address: usize) -> bool {
+        if self.addresses.remove(&address) {
+            return false;
+        }
+        self.addresses.insert(address);
+        true
+    }
+
+    pub fn addresses(&self) -> &BTreeSet<usize> {
+        &self.addresses
+    }
+
+    /// The writes since the last call.
+    pub fn take_hits(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.hits)
+    }
+}
+
+impl Hook for Watchpoints {
+    fn before_instruction(&mut self, state: &MachineState, _opcode: u16) {
+        self.pc = state.pc;
+    }
+
+    fn memory_write(&mut self, address: usize, value: u8) {
+        if self.addresses.contains(&address) {
+            self.hits.push(WatchHit {
+                pc: self.pc,
+                address,
+                value,
+            });
+        }
+    }
+}
+
+pub fn print_help() {
+    println!("{}", HELP);
+}
+
+/// Prints the registers, timers, stack, keys held and the next
+/// instruction.
+pub fn print_registers(chip8: &Chip8) {
+    let state = chip8.state();
+    let pc = state.pc;
+    println!(
+        "PC=0x{:03X} I=0x{:03X} DT={} ST={}",
+        pc, state.index, state.delay_timer, state.sound_timer
+    );
+    let stack: Vec<String> = (state.stack.iter())
+        .map(|address| format!("0x{:03X}", address))
+        .collect();
+    let keys: Vec<String> = (0..16)
+        .filter(|&key| state.keys[key])
+        .map(|key| format!("{:X}", key))
+        .collect();
+    println!("Stack: [{}] Keys: [{}]", stack.join(" "), keys.join(" "));
+    for row in 0..2 {
+        let registers: Vec<String> = (row * 8..row * 8 + 8)
+            .map(|x| format!("V{:X}={:02X}", x, state.v[x]))
+            .collect();
+        println!("{}", registers.join(" "));
+    }
+    let next = state.memory.get(pc..pc + 2).unwrap_or_default();
+    if let Some(line) = disassemble(next, chip8.platform()).first() {
+        println!("0x{:03X}: {}", pc, line.text);
+    }
+}
+
+/// Prints `length` bytes of memory from `address`, 16 to a line.
+pub fn print_memory(chip8: &Chip8, address: usize, length: usize) {
+    let memory = chip8.state().memory;
+    let end = (address + length).min(memory.len());
+    for start in (address.min(end)..end).step_by(16) {
+        let bytes: Vec<String> = (memory[start..end.min(start + 16)].iter())
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        println!("0x{:03X}: {}", start, bytes.join(" "));
+    }
+}
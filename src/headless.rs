@@ -0,0 +1,143 @@
+use crate::achievements::Achievements;
+use crate::captures::Captures;
+use crate::cheats::Cheat;
+use crate::chip8::{matches_png, parse_number, save_png, Chip8, Display, Field};
+use crate::scripting::Script;
+use std::{error::Error, io, path::Path, str::FromStr};
+
+/// What runs alongside the ROM.
+#[derive(Default)]
+pub struct Extras {
+    /// Applied before every frame
+    pub cheats: Vec<Cheat>,
+    /// Checked after every frame
+    pub achievements: Option<Achievements>,
+    /// Run before every frame, after the cheats
+    pub script: Option<Script>,
+}
+
+/// Runs the interpreter for a fixed number of frames without a window or
+/// audio device, as fast as possible, along with the `extras`. Stops early
+/// if the ROM or the script fails.
+pub fn run(
+    chip8: &mut Chip8,
+    display: &mut Display,
+    captures: &mut Captures,
+    extras: &mut Extras,
+    frames: u32,
+    debug: bool,
+) -> Result<(), Box<dyn Error>> {
+    for frame in 0..frames {
+        for cheat in &extras.cheats {
+            cheat.apply(chip8);
+        }
+        let result = match &mut extras.script {
+            Some(script) => script.run_frame(chip8, [false; 16]).map_err(Into::into),
+            None => Ok(()),
+        }
+        .and_then(|()| chip8.run_frame(debug).map_err(Into::into));
+        if let Some(achievements) = &mut extras.achievements {
+            let unlocked = achievements.update(&chip8.state());
+            for achievement in &unlocked {
+                println!("[Achievements] Unlocked {}", achievement);
+            }
+            if !unlocked.is_empty() {
+                if let Err(err) = achievements.save() {
+                    eprintln!("[Achievements] Failed to save: {}", err);
+                }
+            }
+        }
+        display.push(chip8.state().screen);
+        if captures.is_active() {
+            let frame = display.frame_rgba(chip8.state().screen);
+            captures.push(&frame, chip8.sound_frame());
+        }
+        if let Err(err) = result {
+            captures.finish();
+            println!("[Headless] Stopped after {} frames: {}", frame + 1, err);
+            return Err(err);
+        }
+    }
+    captures.finish();
+
+    println!("[Headless] Ran {} frames", frames);
+    Ok(())
+}
+
+pub fn save_screenshot(chip8: &Chip8, display: &Display, path: &Path) -> Result<(), io::Error> {
+    save_png(path, &display.frame_rgba(chip8.state().screen))?;
+    println!("[Capture] Screenshot saved to {}", path.display());
+    Ok(())
+}
+
+/// Runs the interpreter like `run`, then checks the machine state. Returns
+/// whether every check passed.
+pub fn test(
+    chip8: &mut Chip8,
+    display: &mut Display,
+    frames: u32,
+    expectations: &[Expectation],
+    expected_screen: Option<&Path>,
+    debug: bool,
+) -> Result<bool, io::Error> {
+    let mut failures = 0;
+    if let Err(err) = run(
+        chip8,
+        display,
+        &mut Captures::default(),
+        &mut Extras::default(),
+        frames,
+        debug,
+    ) {
+        println!("[Test] FAIL {}", err);
+        failures += 1;
+    }
+
+    for expectation in expectations {
+        let actual = chip8.state().get(expectation.target);
+        if actual != expectation.value {
+            println!(
+                "[Test] FAIL {} = 0x{:X}, expected 0x{:X}",
+                expectation.target, actual, expectation.value
+            );
+            failures += 1;
+        }
+    }
+    if let Some(path) = expected_screen {
+        if !matches_png(path, &display.frame_rgba(chip8.state().screen))? {
+            println!("[Test] FAIL screen doesn't match {}", path.display());
+            failures += 1;
+        }
+    }
+
+    // Running all the frames without an error counts as a check too
+    let checks = expectations.len() + expected_screen.is_some() as usize + 1;
+    if failures == 0 {
+        println!("[Test] PASS {} checks", checks);
+    } else {
+        println!("[Test] {} of {} checks failed", failures, checks);
+    }
+    Ok(failures == 0)
+}
+
+/// A value checked by `test`, written as `TARGET=VALUE`.
+#[derive(Clone, Debug)]
+pub struct Expectation {
+    target: Field,
+    value: usize,
+}
+
+impl FromStr for Expectation {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (target, value) = text
+            .split_once('=')
+            .ok_or_else(|| format!("expected TARGET=VALUE, got `{}`", text))?;
+        let target = target.parse()?;
+        let value = value.trim();
+        let value = parse_number(value).ok_or_else(|| format!("invalid value `{}`", value))?;
+
+        Ok(Self { target, value })
+    }
+}
@@ -1,74 +1,313 @@
 #![forbid(unsafe_code)]
 
+mod achievements;
+mod app;
+mod browser;
+mod captures;
+mod cheats;
 mod chip8;
+mod cli;
+mod debugger;
+mod headless;
+mod profiler;
+mod roms;
+mod scripting;
+mod shader;
+#[cfg(test)]
+mod tests;
+mod text;
+mod tools;
 
-use chip8::{Chip8, SCREEN_HEIGHT, SCREEN_WIDTH};
-use clap::Parser;
+use achievements::Achievements;
+use app::{App, BUFFER_HEIGHT, BUFFER_WIDTH};
+use captures::Captures;
+use cheats::CheatBook;
+use chip8::{
+    open_backend, rom_hash, Beeper, Chip8, Database, Display, DisplayFilter, Palette, LORES_HEIGHT,
+    LORES_WIDTH,
+};
+use cli::{Cli, Command, MachineArgs, RunArgs, TestArgs};
+use debugger::Console;
 use error_iter::ErrorIter as _;
 use game_loop::game_loop;
 use log::error;
-use pixels::{Error, Pixels, SurfaceTexture};
-use std::sync::Arc;
+use pixels::{wgpu, Error, Pixels, PixelsBuilder, SurfaceTexture};
+use profiler::Profile;
+use roms::{RomLibrary, RomSource, RomWatcher};
+use scripting::Script;
+use shader::ShaderRenderer;
+use std::{path::Path, sync::Arc};
 use winit::dpi::{LogicalSize, PhysicalSize};
 use winit::event_loop::EventLoop;
 use winit::window::{Window, WindowBuilder};
 
-const WIDTH: u32 = SCREEN_WIDTH as u32;
-const HEIGHT: u32 = SCREEN_HEIGHT as u32;
-const SCALE: f64 = 12.0;
-const UPS: u32 = 100;
+const WIDTH: u32 = BUFFER_WIDTH as u32;
+const HEIGHT: u32 = BUFFER_HEIGHT as u32;
+/// Smallest `--scale`, so the ROM browser text stays readable
+const MIN_SCALE: f64 = 4.0;
+const UPS: u32 = 60;
 const MAX_FRAME_TIME: f64 = 0.1;
 
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
-    /// Path to the ROM file
-    #[arg(required = true, index = 1)]
-    rom: String,
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Init logging
+    env_logger::init();
 
-    /// Enable debug mode
-    #[arg(short, long, default_value_t = false)]
-    debug: bool,
+    // Parse args
+    let cli = Cli::parse_with_default_command();
+    match cli.command {
+        Command::Run(args) => run(*args),
+        Command::Disasm(args) => tools::disasm(&args),
+        Command::Asm(args) => tools::asm(&args),
+        Command::Info(args) => tools::info(&args),
+        Command::Test(args) => test(args),
+    }
 }
 
-fn main() -> Result<(), Error> {
-    // Init logging
-    env_logger::init();
+/// Loads the ROM with the database settings, then the command line
+/// overrides. If `rom` holds several ROMs, none is loaded yet and the
+/// library is returned to pick from.
+fn load_machine(
+    rom: &str,
+    args: &MachineArgs,
+) -> Result<(Chip8, Option<RomLibrary>), Box<dyn std::error::Error>> {
+    let database = match &args.database {
+        Some(path) => Database::with_overrides(path)?,
+        None => Database::builtin(),
+    };
+    let source = RomSource::open(rom, &database)?;
+
+    let mut chip8 = Chip8::new(database);
+    if let Some(platform) = args.platform {
+        chip8.set_platform(platform);
+    }
+    if let Some(quirks) = &args.quirks {
+        chip8.set_quirk_overrides(quirks.clone());
+    }
+    if let Some(speed) = args.speed {
+        chip8.set_speed(speed);
+    }
+    if let Some(seed) = args.seed {
+        chip8.set_seed(seed);
+    }
+    chip8.set_memory_fill(args.memory_fill);
+    chip8.set_timing(args.timing);
+    chip8.set_stack(args.stack_depth, args.vip_stack);
+    match source {
+        RomSource::Rom(data) => {
+            chip8.load_rom(&data)?;
+            Ok((chip8, None))
+        }
+        RomSource::Library(library) => Ok((chip8, Some(library))),
+    }
+}
 
+/// Like `load_machine`, for modes that can't pick a ROM from a library.
+fn load_single(rom: &str, args: &MachineArgs) -> Result<Chip8, Box<dyn std::error::Error>> {
+    match load_machine(rom, args)? {
+        (chip8, None) => Ok(chip8),
+        (_, Some(library)) => Err(format!(
+            "{} holds {} ROMs; pass one of them instead",
+            rom,
+            library.entries().len()
+        )
+        .into()),
+    }
+}
+
+fn run(args: RunArgs) -> Result<(), Box<dyn std::error::Error>> {
+    if args.headless {
+        return run_headless(args);
+    }
+
+    if args.debugger && args.rom == "-" {
+        return Err(
+            "--debugger reads commands from standard input, so the ROM can't come from it".into(),
+        );
+    }
+    let (chip8, library) = load_machine(&args.rom, &args.machine)?;
+    let watcher = if args.watch {
+        Some(RomWatcher::new(Path::new(&args.rom))?)
+    } else {
+        None
+    };
+    let display = Display::new(chip8.rom_config().choose_palette(args.palette), args.filter);
+    let beeper = beeper(&args);
+    let captures = start_captures(&args, beeper)?;
+    run_windowed(args, beeper, chip8, library, watcher, display, captures)?;
+    Ok(())
+}
+
+fn run_headless(args: RunArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mut chip8 = load_single(&args.rom, &args.machine)?;
+    let mut display = Display::new(chip8.rom_config().choose_palette(args.palette), args.filter);
+    let mut captures = start_captures(&args, beeper(&args))?;
+    let profile = Profile::start(&mut chip8, args.profile.clone(), args.heatmap.clone());
+    let cheats = CheatBook::load(&args.cheats)?
+        .cheats(&rom_hash(chip8.rom()))
+        .to_vec();
+    let mut achievements = Achievements::load(&args.achievements, &args.unlocked)?;
+    achievements.select_rom(chip8.rom());
+    let script = match &args.script {
+        Some(path) => Some(Script::load(path, &mut chip8)?),
+        None => None,
+    };
+    let mut extras = headless::Extras {
+        cheats,
+        achievements: Some(achievements),
+        script,
+    };
+    let result = headless::run(
+        &mut chip8,
+        &mut display,
+        &mut captures,
+        &mut extras,
+        args.frames,
+        args.machine.debug,
+    );
+    // The profile is saved even if the ROM failed, to help find out why
+    if let Some(profile) = profile {
+        profile.finish(&mut chip8)?;
+    }
+    result?;
+    if let Some(path) = &args.screenshot {
+        headless::save_screenshot(&chip8, &display, path)?;
+    }
+    Ok(())
+}
+
+fn beeper(args: &RunArgs) -> Beeper {
+    Beeper {
+        waveform: args.waveform,
+        frequency: args.frequency,
+        volume: args.volume.clamp(0.0, 1.0),
+    }
+}
+
+fn start_captures(args: &RunArgs, beeper: Beeper) -> Result<Captures, std::io::Error> {
+    let mut captures = Captures::default();
+    if let Some(path) = &args.record {
+        captures.start_recording(path.clone(), args.record_format);
+    }
+    if let Some(base) = &args.record_av {
+        captures.start_av_recording(base, beeper)?;
+    }
+    Ok(captures)
+}
+
+fn test(args: TestArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mut chip8 = load_single(&args.rom, &args.machine)?;
+    // Screenshots are compared in the default palette, whatever the
+    // database picks for the ROM
+    let mut display = Display::new(Palette::default(), DisplayFilter::None);
+    let passed = headless::test(
+        &mut chip8,
+        &mut display,
+        args.frames,
+        &args.expect,
+        args.expect_screen.as_deref(),
+        args.machine.debug,
+    )?;
+    if !passed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_windowed(
+    args: RunArgs,
+    beeper: Beeper,
+    mut chip8: Chip8,
+    library: Option<RomLibrary>,
+    watcher: Option<RomWatcher>,
+    display: Display,
+    captures: Captures,
+) -> Result<(), Error> {
     // Init winit and pixels
     let event_loop = EventLoop::new();
-    let window = build_window(&event_loop);
-    let pixels = {
-        let window_size = window.inner_size();
-        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(WIDTH, HEIGHT, surface_texture)?
+    let window = build_window(&event_loop, args.scale.max(MIN_SCALE));
+    if let Some(title) = chip8.title() {
+        window.set_title(&format!("CHIP-8 - {}", title));
+    }
+    let pixels = build_pixels(&window, args.software)?;
+    let mut shader = if args.shader.is_none() {
+        None
+    } else {
+        ShaderRenderer::new(&pixels, args.shader)
     };
 
-    // Parse args
-    let args = Args::parse();
-    let rom_path = args.rom;
-    let debug = args.debug;
-
-    // Load ROM
-    let mut chip8 = Chip8::new(pixels);
-    chip8.load_file(&rom_path).unwrap();
+    let profile = Profile::start(&mut chip8, args.profile.clone(), args.heatmap.clone());
+    let script = args.script.as_ref().and_then(|path| {
+        Script::load(path, &mut chip8)
+            .map_err(|err| eprintln!("[Script] Failed to load {}: {}", path.display(), err))
+            .ok()
+    });
+    let audio = open_backend(args.audio, &args.audio_file, beeper);
+    let mut app = App::new(chip8, pixels, display, audio, captures);
+    app.set_debug(args.machine.debug);
+    app.set_volume(beeper.volume);
+    app.set_capture_dir(args.capture_dir, args.record_format);
+    if let Some(library) = library {
+        app.set_library(library, false);
+    }
+    app.set_palette(args.palette);
+    if let Some(watcher) = watcher {
+        app.set_watcher(watcher, args.keep_keys, args.keep_breakpoints);
+    }
+    if args.debugger {
+        app.set_console(Console::spawn());
+    }
+    if let Some(profile) = profile {
+        app.set_profile(profile);
+    }
+    if let Some(script) = script {
+        app.set_script(script);
+    }
+    match CheatBook::load(&args.cheats) {
+        Ok(book) => app.set_cheat_book(book),
+        Err(err) => eprintln!("[Cheats] Failed to load {}: {}", args.cheats.display(), err),
+    }
+    match Achievements::load(&args.achievements, &args.unlocked) {
+        Ok(achievements) => app.set_achievements(achievements),
+        Err(err) => eprintln!("[Achievements] Failed to load: {}", err),
+    }
+    let screenshot = args.screenshot;
 
-    type Game = game_loop::GameLoop<Chip8, game_loop::Time, Arc<winit::window::Window>>;
+    type Game = game_loop::GameLoop<App, game_loop::Time, Arc<winit::window::Window>>;
     type GameEvent<'a> = winit::event::Event<'a, ()>;
-    let update = move |g: &mut Game| g.game.run_cycle(debug);
-    let render = |g: &mut Game| {
+    let update = |g: &mut Game| g.game.update();
+    let render = move |g: &mut Game| {
         g.game.render();
-        if let Err(err) = g.game.pixels.render() {
+        let pixels = &g.game.pixels;
+        let result = match shader.as_mut() {
+            Some(shader) => {
+                let size = g.window.inner_size();
+                pixels.render_with(|encoder, render_target, context| {
+                    shader.render(encoder, render_target, context, size);
+                    Ok(())
+                })
+            }
+            None => pixels.render(),
+        };
+        if let Err(err) = result {
             log_error("pixels.render", err);
             g.exit();
         }
     };
-    let handle_events = |g: &mut Game, event: &GameEvent| {
+    let handle_events = move |g: &mut Game, event: &GameEvent| {
         if !g.game.input.update(event) {
             return;
         }
         g.game.update_controls();
+        if let Some(title) = g.game.take_title() {
+            g.window.set_title(&title);
+        }
         if g.game.should_close() {
+            if let Some(path) = &screenshot {
+                if let Err(err) = g.game.save_screenshot(path) {
+                    log_error("save_screenshot", err);
+                }
+            }
+            g.game.finish();
             g.exit();
             return;
         }
@@ -84,7 +323,7 @@ fn main() -> Result<(), Error> {
     game_loop(
         event_loop,
         Arc::new(window),
-        chip8,
+        app,
         UPS,
         MAX_FRAME_TIME,
         update,
@@ -100,9 +339,24 @@ fn log_error<E: std::error::Error + 'static>(method_name: &str, err: E) {
     }
 }
 
-fn build_window(event_loop: &EventLoop<()>) -> Window {
-    let logical_size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
-    let scaled_size: PhysicalSize<f64> = logical_size.to_physical(SCALE);
+fn build_pixels(window: &Window, software: bool) -> Result<Pixels, Error> {
+    let window_size = window.inner_size();
+    let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, window);
+    let mut builder = PixelsBuilder::new(WIDTH, HEIGHT, surface_texture);
+    if software {
+        builder = builder.request_adapter_options(wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::LowPower,
+            force_fallback_adapter: true,
+            compatible_surface: None,
+        });
+    }
+    builder.build()
+}
+
+fn build_window(event_loop: &EventLoop<()>, scale: f64) -> Window {
+    // `--scale` is in CHIP-8 pixels
+    let logical_size = LogicalSize::new(LORES_WIDTH as f64, LORES_HEIGHT as f64);
+    let scaled_size: PhysicalSize<f64> = logical_size.to_physical(scale);
     WindowBuilder::new()
         .with_title("CHIP-8")
         .with_inner_size(scaled_size)
@@ -0,0 +1,265 @@
+use crate::chip8::{disassemble, save_image, Chip8, Hook, HookId, MachineState, Platform};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    io::{self, Write as _},
+    path::PathBuf,
+    rc::Rc,
+};
+
+/// Bytes of memory, and cells of the heatmap
+const MEMORY_SIZE: usize = 0x1000;
+/// Cells in each row of the heatmap
+const HEATMAP_COLUMNS: usize = 64;
+/// Size of a heatmap cell in pixels
+const HEATMAP_SCALE: usize = 8;
+/// Loops listed in the report
+const HOT_LOOPS: usize = 10;
+/// Width of the instruction column in the annotated disassembly
+const LISTING_WIDTH: usize = 24;
+
+/// Hook counting how often each address is executed, read and written, and
+/// how often each backward jump is taken.
+pub struct Profiler {
+    executed: Vec<u64>,
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+    /// Times each backward jump was taken, by its target and the address
+    /// of the jump
+    loops: HashMap<(usize, usize), u64>,
+    instructions: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            executed: vec![0; MEMORY_SIZE],
+            reads: vec![0; MEMORY_SIZE],
+            writes: vec![0; MEMORY_SIZE],
+            loops: HashMap::new(),
+            instructions: 0,
+        }
+    }
+
+    /// Coverage of `rom`, its hottest loops and code that never ran, and
+    /// its disassembly annotated with how often each line ran.
+    pub fn report(&self, rom: &[u8], platform: Platform) -> String {
+        let lines = disassemble(rom, platform);
+        let covered = (lines.iter())
+            .filter(|line| self.executed[line.address] > 0)
+            .count();
+        let mut report = format!(
+            "Executed {} instructions, covering {} of {} ROM words ({:.1}%)\n",
+            self.instructions,
+            covered,
+            lines.len(),
+            100.0 * covered as f64 / lines.len().max(1) as f64
+        );
+
+        report.push_str("\nHottest loops:\n");
+        for (start, end, taken, executed) in self.hot_loops() {
+            let _ = writeln!(
+                report,
+                "  0x{:03X}-0x{:03X}  {:>10} times  {:>5.1}% of instructions",
+                start,
+                end,
+                taken,
+                100.0 * executed as f64 / self.instructions.max(1) as f64
+            );
+        }
+
+        report.push_str("\nNever executed:\n");
+        let unexecuted = |i: &usize| self.executed[lines[*i].address] == 0;
+        let mut i = 0;
+        while i < lines.len() {
+            if !unexecuted(&i) {
+                i += 1;
+                continue;
+            }
+            let end = (i..lines.len())
+                .find(|j| !unexecuted(j))
+                .unwrap_or(lines.len());
+            let start = lines[i].address;
+            let end_address = lines[end - 1].address + lines[end - 1].bytes.len();
+            let _ = writeln!(
+                report,
+                "  0x{:03X}-0x{:03X}  {} bytes",
+                start,
+                end_address - 1,
+                end_address - start
+            );
+            i = end;
+        }
+
+        report.push_str("\nAnnotated disassembly:\n");
+        for line in &lines {
+            let hits = match self.executed[line.address] {
+                0 => "-".to_string(),
+                hits => hits.to_string(),
+            };
+            let _ = writeln!(
+                report,
+                "{:>12}  {:<width$}; 0x{:03X}",
+                hits,
+                line.text,
+                line.address,
+                width = LISTING_WIDTH
+            );
+        }
+        report
+    }
+
+    /// The loops that ran the most instructions, as their start and end
+    /// addresses, times their jump back was taken and instructions run
+    /// inside them.
+    pub fn hot_loops(&self) -> Vec<(usize, usize, u64, u64)> {
+        let mut loops: Vec<_> = (self.loops.iter())
+            .map(|(&(start, end), &taken)| {
+                let executed: u64 = self.executed[start..(end + 2).min(MEMORY_SIZE)]
+                    .iter()
+                    .sum();
+                (start, end, taken, executed)
+            })
+            .collect();
+        loops.sort_by(|a, b| b.3.cmp(&a.3).then(a.0.cmp(&b.0)));
+        loops.truncate(HOT_LOOPS);
+        loops
+    }
+
+    /// Memory as a grid of 64 bytes per row: executed bytes glow red to
+    /// yellow with how often they ran, data read or written glows blue, and
+    /// the rest of the ROM is dark blue.
+    pub fn heatmap(&self, rom_len: usize) -> (usize, usize, Vec<u8>) {
+        let rom = 0x200..0x200 + rom_len;
+        // How hot a count is relative to the largest, on a log scale
+        let heat = |counts: &[u64]| {
+            let max = ((counts.iter().max().unwrap_or(&0) + 1) as f32).ln();
+            let heat: Vec<f32> = (counts.iter())
+                .map(|&count| ((count + 1) as f32).ln() / max.max(f32::EPSILON))
+                .collect();
+            heat
+        };
+        let accessed: Vec<u64> = (self.reads.iter().zip(&self.writes))
+            .map(|(reads, writes)| reads + writes)
+            .collect();
+        let (executed, accessed) = (heat(&self.executed), heat(&accessed));
+        let cells: Vec<[u8; 4]> = (0..MEMORY_SIZE)
+            .map(|address| {
+                // An instruction covers its address and the byte after it
+                let executed = executed[address].max(executed[address.saturating_sub(1)]);
+                let accessed = accessed[address];
+                let level = |low: f32, high: f32, t: f32| (low + (high - low) * t) as u8;
+                if executed > 0.0 {
+                    [
+                        level(96.0, 255.0, executed),
+                        level(0.0, 255.0, executed * executed),
+                        0,
+                        255,
+                    ]
+                } else if accessed > 0.0 {
+                    [
+                        0,
+                        level(32.0, 160.0, accessed),
+                        level(96.0, 255.0, accessed),
+                        255,
+                    ]
+                } else if rom.contains(&address) {
+                    [24, 24, 48, 255]
+                } else {
+                    [12, 12, 12, 255]
+                }
+            })
+            .collect();
+
+        let width = HEATMAP_COLUMNS * HEATMAP_SCALE;
+        let height = MEMORY_SIZE / HEATMAP_COLUMNS * HEATMAP_SCALE;
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        for row in cells.chunks_exact(HEATMAP_COLUMNS) {
+            for _ in 0..HEATMAP_SCALE {
+                for cell in row {
+                    for _ in 0..HEATMAP_SCALE {
+                        rgba.extend_from_slice(cell);
+                    }
+                }
+            }
+        }
+        (width, height, rgba)
+    }
+}
+
+impl Hook for Profiler {
+    fn before_instruction(&mut self, state: &MachineState, _opcode: u16) {
+        self.executed[state.pc % MEMORY_SIZE] += 1;
+        self.instructions += 1;
+    }
+
+    fn after_instruction(&mut self, state: &MachineState, address: usize, opcode: u16) {
+        // Jumping back is a loop, but returning from or calling a
+        // subroutine isn't
+        let call_or_return = opcode == 0x00EE || opcode & 0xF000 == 0x2000;
+        if state.pc <= address && !call_or_return {
+            *self.loops.entry((state.pc, address)).or_insert(0) += 1;
+        }
+    }
+
+    fn memory_read(&mut self, address: usize, _value: u8) {
+        self.reads[address % MEMORY_SIZE] += 1;
+    }
+
+    fn memory_write(&mut self, address: usize, _value: u8) {
+        self.writes[address % MEMORY_SIZE] += 1;
+    }
+}
+
+/// A profiler hooked into the interpreter, and where to save what it found.
+pub struct Profile {
+    profiler: Rc<RefCell<Profiler>>,
+    hook: HookId,
+    /// Report file, or standard output for `-`
+    report: Option<PathBuf>,
+    heatmap: Option<PathBuf>,
+}
+
+impl Profile {
+    /// Starts profiling `chip8` if a report or heatmap was asked for.
+    pub fn start(
+        chip8: &mut Chip8,
+        report: Option<PathBuf>,
+        heatmap: Option<PathBuf>,
+    ) -> Option<Self> {
+        if report.is_none() && heatmap.is_none() {
+            return None;
+        }
+        let profiler = Rc::new(RefCell::new(Profiler::new()));
+        let hook = chip8.add_hook(Box::new(Rc::clone(&profiler)));
+        Some(Self {
+            profiler,
+            hook,
+            report,
+            heatmap,
+        })
+    }
+
+    /// Stops profiling and saves the report and heatmap.
+    pub fn finish(self, chip8: &mut Chip8) -> Result<(), io::Error> {
+        chip8.remove_hook(self.hook);
+        let profiler = self.profiler.borrow();
+        if let Some(path) = &self.report {
+            let report = profiler.report(chip8.rom(), chip8.platform());
+            if path.as_os_str() == "-" {
+                io::stdout().write_all(report.as_bytes())?;
+            } else {
+                fs::write(path, report)?;
+                println!("[Profile] Report saved to {}", path.display());
+            }
+        }
+        if let Some(path) = &self.heatmap {
+            let (width, height, rgba) = profiler.heatmap(chip8.rom().len());
+            save_image(path, width, height, &rgba)?;
+            println!("[Profile] Heatmap saved to {}", path.display());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use crate::chip8::Database;
+use std::{
+    fs::{self, File},
+    io::{self, Error, ErrorKind, Read},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+use zip::ZipArchive;
+
+/// File extensions of CHIP-8, SUPER-CHIP and XO-CHIP ROMs
+const EXTENSIONS: [&str; 5] = ["ch8", "c8", "sc8", "xo8", "rom"];
+/// How often a watched ROM file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What a ROM argument points at.
+pub enum RomSource {
+    /// A single ROM, read from a file, standard input or a zip archive
+    Rom(Vec<u8>),
+    /// Several ROMs to pick from
+    Library(RomLibrary),
+}
+
+impl RomSource {
+    /// Opens `-` (standard input), a ROM file, a directory, or a zip archive
+    /// (a library unless it holds a single ROM).
+    pub fn open(path: &str, database: &Database) -> Result<Self, Error> {
+        if path == "-" {
+            let mut rom = vec![];
+            io::stdin().read_to_end(&mut rom)?;
+            return Ok(RomSource::Rom(rom));
+        }
+
+        let path = Path::new(path);
+        if !path.is_dir() && !is_zip(path) {
+            return Ok(RomSource::Rom(fs::read(path)?));
+        }
+
+        let mut library = RomLibrary::open(path, database)?;
+        if library.entries.len() == 1 {
+            return Ok(RomSource::Rom(library.load(0)?));
+        }
+        Ok(RomSource::Library(library))
+    }
+}
+
+/// ROMs in a directory or zip archive.
+pub struct RomLibrary {
+    path: PathBuf,
+    archive: Option<ZipArchive<File>>,
+    entries: Vec<RomEntry>,
+}
+
+pub struct RomEntry {
+    /// File name, relative to the library
+    pub name: String,
+    /// Title from the ROM database, if it is known
+    pub title: Option<String>,
+}
+
+impl RomLibrary {
+    pub fn open(path: &Path, database: &Database) -> Result<Self, Error> {
+        let mut library = Self {
+            path: path.to_path_buf(),
+            archive: None,
+            entries: vec![],
+        };
+
+        let mut names: Vec<String> = if path.is_dir() {
+            fs::read_dir(path)?
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_file())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect()
+        } else {
+            let archive = ZipArchive::new(File::open(path)?).map_err(Error::other)?;
+            let names = archive.file_names().map(str::to_string).collect();
+            library.archive = Some(archive);
+            names
+        };
+        names.retain(|name| is_rom(Path::new(name)));
+        names.sort_by_key(|name| name.to_lowercase());
+
+        for name in names {
+            let title = library
+                .read(&name)
+                .ok()
+                .and_then(|rom| database.lookup(&rom)?.title.clone());
+            library.entries.push(RomEntry { name, title });
+        }
+
+        if library.entries.is_empty() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("no ROMs found in {}", path.display()),
+            ));
+        }
+        Ok(library)
+    }
+
+    pub fn entries(&self) -> &[RomEntry] {
+        &self.entries
+    }
+
+    pub fn load(&mut self, index: usize) -> Result<Vec<u8>, Error> {
+        let name = self.entries[index].name.clone();
+        self.read(&name)
+    }
+
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, Error> {
+        match &mut self.archive {
+            Some(archive) => {
+                let mut file = archive.by_name(name).map_err(Error::other)?;
+                let mut rom = vec![];
+                file.read_to_end(&mut rom)?;
+                Ok(rom)
+            }
+            None => fs::read(self.path.join(name)),
+        }
+    }
+}
+
+/// Notices when a ROM file is rewritten, e.g. by an assembler.
+pub struct RomWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl RomWatcher {
+    pub fn new(path: &Path) -> Result<Self, Error> {
+        if path.is_dir() || is_zip(path) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("can't watch {}, only a single ROM file", path.display()),
+            ));
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            modified: fs::metadata(path)?.modified().ok(),
+            last_check: Instant::now(),
+        })
+    }
+
+    /// The new contents of the file if it changed since the last call.
+    /// Checks at most every `WATCH_INTERVAL`, and skips a file that is empty
+    /// or can't be read (it may be half-written) until the next check.
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        if self.last_check.elapsed() < WATCH_INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        let modified = fs::metadata(&self.path).ok()?.modified().ok();
+        if modified == self.modified {
+            return None;
+        }
+        let rom = fs::read(&self.path).ok().filter(|rom| !rom.is_empty())?;
+        self.modified = modified;
+        Some(rom)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn is_zip(path: &Path) -> bool {
+    has_extension(path, &["zip"])
+}
+
+fn is_rom(path: &Path) -> bool {
+    has_extension(path, &EXTENSIONS)
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extensions
+                .iter()
+                .any(|candidate| extension.eq_ignore_ascii_case(candidate))
+        })
+}
@@ -0,0 +1,290 @@
+use crate::chip8::{Chip8, Field, Hook, HookId, Keys, MachineState};
+use crate::text::{Canvas, CHAR_WIDTH, LINE_HEIGHT};
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs, Map, Scope, AST, INT};
+use std::{
+    cell::RefCell,
+    fs,
+    io::{Error, ErrorKind},
+    path::Path,
+    rc::Rc,
+};
+
+const TEXT: [u8; 4] = [0xf2, 0xf2, 0xf2, 0xff];
+const BACKGROUND: [u8; 4] = [0x20, 0x1c, 0x30, 0xff];
+
+pub type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// The machine as the script sees it: a copy taken before each call with
+/// the script's own writes applied, the writes to make once it returns and
+/// what it asked to draw.
+#[derive(Default)]
+struct Context {
+    v: [u8; 16],
+    index: usize,
+    pc: usize,
+    delay_timer: u8,
+    sound_timer: u8,
+    memory: Vec<u8>,
+    keys: Keys,
+    writes: Vec<(Field, usize)>,
+    /// Keys the script holds down, on top of the keyboard
+    held: Keys,
+    /// Lines of text drawn over the screen this frame, by their top left
+    /// corner
+    texts: Vec<(usize, usize, String)>,
+}
+
+impl Context {
+    fn load(&mut self, state: &MachineState) {
+        self.v = state.v;
+        self.index = state.index;
+        self.pc = state.pc;
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        self.memory.clear();
+        self.memory.extend_from_slice(state.memory);
+        self.keys = state.keys;
+    }
+
+    fn get(&self, field: Field) -> usize {
+        match field {
+            Field::Register(x) => self.v[x] as usize,
+            Field::Index => self.index,
+            Field::ProgramCounter => self.pc,
+            Field::DelayTimer => self.delay_timer as usize,
+            Field::SoundTimer => self.sound_timer as usize,
+            Field::Memory(address) => self.memory[address] as usize,
+        }
+    }
+
+    fn set(&mut self, field: Field, value: usize) {
+        match field {
+            Field::Register(x) => self.v[x] = value as u8,
+            Field::Index => self.index = value,
+            Field::ProgramCounter => self.pc = value,
+            Field::DelayTimer => self.delay_timer = value as u8,
+            Field::SoundTimer => self.sound_timer = value as u8,
+            Field::Memory(address) => self.memory[address] = value as u8,
+        }
+        self.writes.push((field, value));
+    }
+
+    fn hold(&mut self, key: usize, held: bool) {
+        self.held[key] = held;
+        self.keys[key] = held;
+    }
+}
+
+/// Checks a number from the script is below `limit`.
+fn index(value: INT, limit: usize, what: &str) -> ScriptResult<usize> {
+    (usize::try_from(value).ok())
+        .filter(|&i| i < limit)
+        .ok_or_else(|| format!("invalid {} {}", what, value).into())
+}
+
+fn value(value: INT) -> ScriptResult<usize> {
+    usize::try_from(value).map_err(|_| format!("invalid value {}", value).into())
+}
+
+/// Checks a number from the script fits in `field`, rather than wrapping it.
+fn field_value(field: Field, value: INT) -> ScriptResult<usize> {
+    let what = match field {
+        Field::Index | Field::ProgramCounter => "address",
+        _ => "value",
+    };
+    index(value, field.max() + 1, what)
+}
+
+/// An engine with the functions scripts use to reach the machine through
+/// `context`.
+fn engine(context: &Rc<RefCell<Context>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.on_print(|text| println!("[Script] {}", text));
+
+    let c = Rc::clone(context);
+    engine.register_fn("get", move |name: &str| -> ScriptResult<INT> {
+        let field: Field = name.parse()?;
+        Ok(c.borrow().get(field) as INT)
+    });
+    let c = Rc::clone(context);
+    engine.register_fn("set", move |name: &str, n: INT| -> ScriptResult<()> {
+        let field: Field = name.parse()?;
+        c.borrow_mut().set(field, field_value(field, n)?);
+        Ok(())
+    });
+    let c = Rc::clone(context);
+    engine.register_fn("peek", move |address: INT| -> ScriptResult<INT> {
+        let address = index(address, c.borrow().memory.len(), "address")?;
+        Ok(c.borrow().memory[address] as INT)
+    });
+    let c = Rc::clone(context);
+    engine.register_fn("poke", move |address: INT, n: INT| -> ScriptResult<()> {
+        let address = index(address, c.borrow().memory.len(), "address")?;
+        let field = Field::Memory(address);
+        c.borrow_mut().set(field, field_value(field, n)?);
+        Ok(())
+    });
+    let c = Rc::clone(context);
+    engine.register_fn("pressed", move |key: INT| -> ScriptResult<bool> {
+        Ok(c.borrow().keys[index(key, 16, "key")?])
+    });
+    let c = Rc::clone(context);
+    engine.register_fn("press", move |key: INT| -> ScriptResult<()> {
+        c.borrow_mut().hold(index(key, 16, "key")?, true);
+        Ok(())
+    });
+    let c = Rc::clone(context);
+    engine.register_fn("release", move |key: INT| -> ScriptResult<()> {
+        c.borrow_mut().hold(index(key, 16, "key")?, false);
+        Ok(())
+    });
+    let c = Rc::clone(context);
+    engine.register_fn(
+        "text",
+        move |x: INT, y: INT, text: &str| -> ScriptResult<()> {
+            let (x, y) = (value(x)?, value(y)?);
+            c.borrow_mut().texts.push((x, y, text.to_string()));
+            Ok(())
+        },
+    );
+    engine
+}
+
+/// Makes the writes the script asked for.
+fn apply_writes(context: &RefCell<Context>, chip8: &mut Chip8) {
+    for (field, value) in context.borrow_mut().writes.drain(..) {
+        chip8.set(field, value);
+    }
+}
+
+/// A compiled script, and the map its functions share as `this` to keep
+/// values between calls.
+struct Runner {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    this: Dynamic,
+    context: Rc<RefCell<Context>>,
+    /// The error `on_instruction` ran into, which stops it being called
+    error: Option<Box<EvalAltResult>>,
+}
+
+impl Runner {
+    /// Calls the script function `name` on the machine as `state` shows it.
+    fn call(&mut self, state: &MachineState, name: &str, args: impl FuncArgs) -> ScriptResult<()> {
+        self.context.borrow_mut().load(state);
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.this);
+        (self.engine)
+            .call_fn_with_options::<Dynamic>(options, &mut self.scope, &self.ast, name, args)
+            .map(drop)
+    }
+}
+
+impl Hook for Runner {
+    fn before_instruction(&mut self, state: &MachineState, opcode: u16) {
+        if self.error.is_some() {
+            return;
+        }
+        let args = (state.pc as INT, opcode as INT);
+        if let Err(err) = self.call(state, "on_instruction", args) {
+            self.error = Some(err);
+        }
+    }
+
+    fn take_writes(&mut self) -> Vec<(Field, usize)> {
+        std::mem::take(&mut self.context.borrow_mut().writes)
+    }
+}
+
+/// A Rhai script driving the machine: reading and writing its registers
+/// and memory, pressing keys and drawing text over the screen from the
+/// functions it defines, `on_frame(frame)` before each frame and
+/// `on_instruction(pc, opcode)` before each instruction.
+pub struct Script {
+    runner: Rc<RefCell<Runner>>,
+    /// The runner's hook, added if the script defines `on_instruction`
+    hook: Option<HookId>,
+    on_frame: bool,
+    /// Frames run so far; the top level runs before the first
+    frames: u64,
+}
+
+impl Script {
+    /// Compiles the script in `path`, hooking it into `chip8`.
+    pub fn load(path: &Path, chip8: &mut Chip8) -> Result<Self, Error> {
+        let source = fs::read_to_string(path)?;
+        let context = Rc::default();
+        let engine = engine(&context);
+        let ast = engine.compile(source).map_err(|err| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), err),
+            )
+        })?;
+        let defines = |name: &str| ast.iter_functions().any(|function| function.name == name);
+        let (on_frame, on_instruction) = (defines("on_frame"), defines("on_instruction"));
+
+        let runner = Rc::new(RefCell::new(Runner {
+            engine,
+            ast,
+            scope: Scope::new(),
+            this: Map::new().into(),
+            context,
+            error: None,
+        }));
+        let hook = on_instruction.then(|| chip8.add_hook(Box::new(Rc::clone(&runner))));
+        Ok(Self {
+            runner,
+            hook,
+            on_frame,
+            frames: 0,
+        })
+    }
+
+    /// Runs the script's top level before the first frame and `on_frame`
+    /// before each one, then makes the writes it asked for and holds the
+    /// keys it pressed on top of those held on the `keyboard`. Fails on
+    /// the first error the script ran into since the last frame.
+    pub fn run_frame(&mut self, chip8: &mut Chip8, keyboard: Keys) -> ScriptResult<()> {
+        let mut runner = self.runner.borrow_mut();
+        let runner = &mut *runner;
+        if let Some(err) = runner.error.take() {
+            return Err(err);
+        }
+        runner.context.borrow_mut().texts.clear();
+        if self.frames == 0 {
+            runner.context.borrow_mut().load(&chip8.state());
+            (runner.engine).run_ast_with_scope(&mut runner.scope, &runner.ast)?;
+            apply_writes(&runner.context, chip8);
+        }
+        if self.on_frame {
+            runner.call(&chip8.state(), "on_frame", (self.frames as INT,))?;
+            apply_writes(&runner.context, chip8);
+        }
+        self.frames += 1;
+
+        let held = runner.context.borrow().held;
+        chip8.set_keys(std::array::from_fn(|key| keyboard[key] || held[key]));
+        Ok(())
+    }
+
+    /// Draws the text the script asked for this frame.
+    pub fn draw(&self, canvas: &mut Canvas) {
+        let runner = self.runner.borrow();
+        for (x, y, text) in &runner.context.borrow().texts {
+            let width = text.chars().count() * CHAR_WIDTH + 1;
+            let (left, top) = (x.saturating_sub(1), y.saturating_sub(1));
+            canvas.fill_rect(left, top, width, LINE_HEIGHT, BACKGROUND);
+            canvas.draw_text(*x, *y, text, TEXT);
+        }
+    }
+
+    /// Unhooks the script from `chip8`.
+    pub fn stop(self, chip8: &mut Chip8) {
+        if let Some(id) = self.hook {
+            chip8.remove_hook(id);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use crate::chip8::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use pixels::{wgpu, Pixels, PixelsContext};
+use std::str::FromStr;
+use winit::dpi::PhysicalSize;
+
+/// Size of the `Locals` uniform in `shader.wgsl`
+const UNIFORM_SIZE: u64 = 12 * 4;
+
+/// Strength of each post-processing effect, 0 disables it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Effects {
+    pub scanlines: f32,
+    pub curvature: f32,
+    pub bloom: f32,
+    pub grid: f32,
+}
+
+impl Effects {
+    pub fn is_none(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl FromStr for Effects {
+    type Err = String;
+
+    /// Parses a comma-separated list of effects, e.g. `scanlines,bloom`.
+    fn from_str(list: &str) -> Result<Self, Self::Err> {
+        let mut effects = Effects::default();
+        for name in list
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            match name.to_ascii_lowercase().as_str() {
+                "none" => {}
+                "scanlines" => effects.scanlines = 1.0,
+                "curvature" => effects.curvature = 1.0,
+                "bloom" => effects.bloom = 0.6,
+                "grid" => effects.grid = 1.0,
+                "crt" => {
+                    effects.scanlines = 1.0;
+                    effects.curvature = 1.0;
+                    effects.bloom = 0.6;
+                }
+                _ => {
+                    return Err(format!(
+                        "unknown shader `{}` (expected scanlines, curvature, bloom, grid or crt)",
+                        name
+                    ))
+                }
+            }
+        }
+        Ok(effects)
+    }
+}
+
+/// Renders the scaled screen to an intermediate texture, then draws it to
+/// the surface through `shader.wgsl`.
+pub struct ShaderRenderer {
+    effects: Effects,
+    texture_format: wgpu::TextureFormat,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    target: Option<Target>,
+}
+
+struct Target {
+    width: u32,
+    height: u32,
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+impl ShaderRenderer {
+    /// Builds the shader pipeline, or returns `None` (after logging why) when
+    /// the adapter rejects it, so the caller can fall back to plain scaling.
+    pub fn new(pixels: &Pixels, effects: Effects) -> Option<Self> {
+        let device = pixels.device();
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let module = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("shader_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shader_uniform_buffer"),
+            size: UNIFORM_SIZE,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("shader_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(UNIFORM_SIZE),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("shader_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("shader_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: pixels.surface_texture_format(),
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        if let Some(err) = pollster::block_on(device.pop_error_scope()) {
+            eprintln!("[Shader] Falling back to plain scaling: {}", err);
+            return None;
+        }
+
+        Some(Self {
+            effects,
+            texture_format: pixels.render_texture_format(),
+            sampler,
+            uniform_buffer,
+            bind_group_layout,
+            pipeline,
+            target: None,
+        })
+    }
+
+    /// Draws the screen with the effects applied, `size` being the current
+    /// surface size.
+    pub fn render(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        render_target: &wgpu::TextureView,
+        context: &PixelsContext,
+        size: PhysicalSize<u32>,
+    ) {
+        let PhysicalSize { width, height } = size;
+        let target = self
+            .target
+            .take()
+            .filter(|target| target.width == width && target.height == height)
+            .unwrap_or_else(|| self.create_target(context, width, height));
+
+        // Scale the screen into the intermediate texture first
+        context.scaling_renderer.render(encoder, &target.view);
+
+        let (x, y, clip_width, clip_height) = context.scaling_renderer.clip_rect();
+        let Effects {
+            scanlines,
+            curvature,
+            bloom,
+            grid,
+        } = self.effects;
+        let locals: [f32; 12] = [
+            x as f32,
+            y as f32,
+            clip_width as f32,
+            clip_height as f32,
+            width as f32,
+            height as f32,
+            // Scanlines and the grid follow CHIP-8 pixels, not the texture's
+            SCREEN_WIDTH as f32,
+            SCREEN_HEIGHT as f32,
+            scanlines,
+            curvature,
+            bloom,
+            grid,
+        ];
+        let bytes: Vec<u8> = locals
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        context.queue.write_buffer(&self.uniform_buffer, 0, &bytes);
+
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("shader_render_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: render_target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(&self.pipeline);
+            rpass.set_bind_group(0, &target.bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+
+        self.target = Some(target);
+    }
+
+    fn create_target(&self, context: &PixelsContext, width: u32, height: u32) -> Target {
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("shader_texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.texture_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = context
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("shader_bind_group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+        Target {
+            width,
+            height,
+            view,
+            bind_group,
+        }
+    }
+}
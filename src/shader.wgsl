@@ -0,0 +1,77 @@
+// Post-processing pass over the scaled CHIP-8 screen.
+//
+// The scaling renderer draws the screen into `r_tex_color` at surface size;
+// `clip` is where it ended up, so fragments can be mapped back to emulated
+// pixels for the scanline and grid effects.
+
+struct Locals {
+    // x, y, width, height of the scaled screen, in surface pixels
+    clip: vec4<f32>,
+    // surface width and height, then emulated screen width and height
+    sizes: vec4<f32>,
+    // scanlines, curvature, bloom, grid strengths (0 disables)
+    effects: vec4<f32>,
+}
+
+@group(0) @binding(0) var r_tex_color: texture_2d<f32>;
+@group(0) @binding(1) var r_tex_sampler: sampler;
+@group(0) @binding(2) var<uniform> r_locals: Locals;
+
+const PI: f32 = 3.14159265;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One full-screen triangle
+    let x = f32((index << 1u) & 2u) * 2.0 - 1.0;
+    let y = f32(index & 2u) * 2.0 - 1.0;
+    return vec4<f32>(x, y, 0.0, 1.0);
+}
+
+fn sample(pos: vec2<f32>) -> vec3<f32> {
+    let uv = (r_locals.clip.xy + pos * r_locals.clip.zw) / r_locals.sizes.xy;
+    return textureSampleLevel(r_tex_color, r_tex_sampler, uv, 0.0).rgb;
+}
+
+@fragment
+fn fs_main(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+    let scanlines = r_locals.effects.x;
+    let curvature = r_locals.effects.y;
+    let bloom = r_locals.effects.z;
+    let grid = r_locals.effects.w;
+
+    // Position within the emulated screen, 0 to 1
+    var pos = (frag.xy - r_locals.clip.xy) / r_locals.clip.zw;
+    if curvature > 0.0 {
+        let centered = pos * 2.0 - 1.0;
+        let offset = centered.yx * centered.yx * curvature * 0.25;
+        pos = (centered + centered * offset) * 0.5 + 0.5;
+    }
+    if any(pos < vec2<f32>(0.0)) || any(pos > vec2<f32>(1.0)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    var color = sample(pos);
+
+    if bloom > 0.0 {
+        let texel = 1.0 / r_locals.sizes.zw;
+        var glow = vec3<f32>(0.0);
+        for (var dy = -2; dy <= 2; dy += 1) {
+            for (var dx = -2; dx <= 2; dx += 1) {
+                glow += sample(pos + vec2<f32>(f32(dx), f32(dy)) * texel * 0.75);
+            }
+        }
+        color += glow / 25.0 * bloom;
+    }
+
+    // Position within the current emulated pixel, 0 to 1
+    let cell = fract(pos * r_locals.sizes.zw);
+    if scanlines > 0.0 {
+        color *= mix(1.0, sin(cell.y * PI), scanlines * 0.6);
+    }
+    if grid > 0.0 {
+        let edge = min(sin(cell.x * PI), sin(cell.y * PI));
+        color *= mix(1.0, smoothstep(0.0, 0.35, edge), grid);
+    }
+
+    return vec4<f32>(color, 1.0);
+}
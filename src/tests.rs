@@ -0,0 +1,305 @@
+//! What ROM arguments open to: single ROMs, and libraries of them in
+//! directories and zip archives. Also the changes to a ROM file noticed
+//! while watching it, the loops and dead code found by the profiler, the
+//! conditions achievements unlock on, and scripts driving the machine.
+
+use crate::{
+    achievements::{Achievement, Achievements, Condition},
+    chip8::{assemble, rom_hash, Chip8, Database, Field, Platform},
+    profiler::Profiler,
+    roms::{RomLibrary, RomSource, RomWatcher},
+    scripting::Script,
+};
+use std::{
+    cell::RefCell,
+    fs::{self, File},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    thread,
+    time::{Duration, SystemTime},
+};
+use zip::{write::FileOptions, ZipWriter};
+
+/// An empty directory of its own for each test.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chip8-rs-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+    let mut zip = ZipWriter::new(File::create(path).unwrap());
+    for (name, contents) in files {
+        zip.start_file(*name, FileOptions::default()).unwrap();
+        zip.write_all(contents).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+fn open(path: &Path) -> Result<RomSource, std::io::Error> {
+    RomSource::open(path.to_str().unwrap(), &Database::builtin())
+}
+
+fn library_names(library: &RomLibrary) -> Vec<&str> {
+    (library.entries().iter())
+        .map(|entry| entry.name.as_str())
+        .collect()
+}
+
+#[test]
+fn single_roms_load_directly() {
+    let dir = temp_dir("single");
+    let rom = dir.join("game.ch8");
+    fs::write(&rom, [0x12, 0x00]).unwrap();
+    let Ok(RomSource::Rom(loaded)) = open(&rom) else {
+        panic!("not a ROM");
+    };
+    assert_eq!(loaded, [0x12, 0x00]);
+
+    // Files that aren't ROMs don't count
+    let zip = dir.join("one.zip");
+    write_zip(&zip, &[("README.txt", b"hi"), ("game.ch8", &[0x00, 0xE0])]);
+    let Ok(RomSource::Rom(loaded)) = open(&zip) else {
+        panic!("not a ROM");
+    };
+    assert_eq!(loaded, [0x00, 0xE0]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn several_roms_make_a_library() {
+    let dir = temp_dir("library");
+    let zip = dir.join("games.zip");
+    write_zip(&zip, &[("b.ch8", &[1]), ("A.sc8", &[2]), ("c.xo8", &[3])]);
+    let Ok(RomSource::Library(mut library)) = open(&zip) else {
+        panic!("not a library");
+    };
+    assert_eq!(library_names(&library), ["A.sc8", "b.ch8", "c.xo8"]);
+    assert_eq!(library.load(1).unwrap(), [1]);
+
+    let games = dir.join("games");
+    fs::create_dir(&games).unwrap();
+    fs::write(games.join("pong.ch8"), [4]).unwrap();
+    fs::write(games.join("tetris.ROM"), [5]).unwrap();
+    fs::write(games.join("notes.txt"), [6]).unwrap();
+    let Ok(RomSource::Library(mut library)) = open(&games) else {
+        panic!("not a library");
+    };
+    assert_eq!(library_names(&library), ["pong.ch8", "tetris.ROM"]);
+    assert_eq!(library.load(1).unwrap(), [5]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn archives_without_roms_fail() {
+    let dir = temp_dir("empty");
+    let zip = dir.join("empty.zip");
+    write_zip(&zip, &[("README.txt", b"hi")]);
+    let Err(err) = open(&zip) else {
+        panic!("opened an archive without ROMs");
+    };
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Rewrites `path`, making sure its modification time moves on however
+/// coarse the file system's clock is.
+fn rewrite(path: &Path, contents: &[u8], seconds_later: u64) {
+    fs::write(path, contents).unwrap();
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 + seconds_later);
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+}
+
+#[test]
+fn watchers_see_rom_files_change() {
+    let dir = temp_dir("watch");
+    let rom = dir.join("game.ch8");
+    rewrite(&rom, &[1], 0);
+    let mut watcher = RomWatcher::new(&rom).unwrap();
+    // Checked at most every 250 ms
+    rewrite(&rom, &[2], 1);
+    assert_eq!(watcher.poll(), None);
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(watcher.poll(), Some(vec![2]));
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(watcher.poll(), None);
+
+    // A file still being written is left for the next check
+    rewrite(&rom, &[], 2);
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(watcher.poll(), None);
+    rewrite(&rom, &[3], 3);
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(watcher.poll(), Some(vec![3]));
+
+    // Only single ROM files can be watched
+    assert!(RomWatcher::new(&dir).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn profiles_find_hot_loops_and_dead_code() {
+    let rom = assemble("LD V0, 0\nloop: ADD V0, 1\nSE V0, 10\nJP loop\nend: JP end\nLD V1, 1\nCLS")
+        .unwrap();
+    let mut chip8 = Chip8::new(Database::builtin());
+    chip8.load_rom(&rom).unwrap();
+    let profiler = Rc::new(RefCell::new(Profiler::new()));
+    chip8.add_hook(Box::new(Rc::clone(&profiler)));
+    // Counting to 10 takes 30 instructions, then the end loop runs 5 times
+    for _ in 0..35 {
+        chip8.step(false).unwrap();
+    }
+
+    let profiler = profiler.borrow();
+    // The counting loop jumps back 9 times, running its three instructions
+    // 10, 10 and 9 times
+    assert_eq!(
+        profiler.hot_loops(),
+        [(0x202, 0x206, 9, 29), (0x208, 0x208, 5, 5)]
+    );
+    let report = profiler.report(&rom, Platform::Chip8);
+    assert!(report.starts_with("Executed 35 instructions, covering 5 of 7 ROM words (71.4%)"));
+    assert!(report.contains("Never executed:\n  0x20A-0x20D  4 bytes\n\n"));
+}
+
+/// A machine with V0 and the byte at 0x3F0 set.
+fn machine(v0: usize, byte: usize) -> Chip8 {
+    let mut chip8 = Chip8::new(Database::builtin());
+    chip8.set(Field::Register(0), v0);
+    chip8.set(Field::Memory(0x3F0), byte);
+    chip8
+}
+
+#[test]
+fn conditions_compare_fields_with_values() {
+    let holds = |condition: &str, v0, byte| {
+        let condition: Condition = condition.parse().unwrap();
+        condition.holds(&machine(v0, byte).state())
+    };
+    // The two-character operators aren't read as their first character
+    assert!(holds("V0 >= 3", 3, 0));
+    assert!(!holds("V0 > 3", 3, 0));
+    assert!(holds("V0 <= 3", 3, 0));
+    assert!(!holds("V0 < 3", 3, 0));
+    assert!(holds("V0 != 3", 4, 0));
+    assert!(holds("[0x3F0] == 0x10", 0, 16));
+
+    // Every comparison has to hold
+    assert!(holds("V0 == 1 && [0x3F0] >= 10", 1, 10));
+    assert!(!holds("V0 == 1 && [0x3F0] >= 10", 1, 9));
+    assert!(!holds("V0 == 1 && [0x3F0] >= 10", 2, 10));
+}
+
+#[test]
+fn bad_conditions_are_rejected() {
+    for (condition, error) in [
+        ("V0 = 3", "expected FIELD OP VALUE, got `V0 = 3`"),
+        ("V0 == 1 &&", "expected FIELD OP VALUE, got ``"),
+        ("V0 == three", "invalid value `three`"),
+    ] {
+        assert_eq!(
+            condition.parse::<Condition>().unwrap_err(),
+            error,
+            "{}",
+            condition
+        );
+    }
+    assert!("VG == 1".parse::<Condition>().is_err());
+    assert!("[0x1000] == 1".parse::<Condition>().is_err());
+}
+
+#[test]
+fn achievements_unlock_once_when_their_condition_becomes_true() {
+    let dir = temp_dir("achievements");
+    let rom = [0x12, 0x00];
+    let definitions = dir.join("achievements.toml");
+    let toml = format!(
+        "[[{}]]\nname = \"Ten\"\nwhen = \"V0 >= 10\"\n",
+        rom_hash(&rom).to_uppercase()
+    );
+    fs::write(&definitions, toml).unwrap();
+    let mut achievements = Achievements::load(&definitions, &dir.join("unlocked.toml")).unwrap();
+    achievements.select_rom(&rom);
+    let names = |unlocked: Vec<Achievement>| -> Vec<String> {
+        unlocked
+            .into_iter()
+            .map(|achievement| achievement.name)
+            .collect()
+    };
+
+    // True from the start doesn't count until it has been false
+    assert!(achievements.update(&machine(10, 0).state()).is_empty());
+    assert!(achievements.update(&machine(0, 0).state()).is_empty());
+    assert_eq!(names(achievements.update(&machine(10, 0).state())), ["Ten"]);
+    // And once it has unlocked, it stays that way
+    assert!(achievements.update(&machine(0, 0).state()).is_empty());
+    assert!(achievements.update(&machine(11, 0).state()).is_empty());
+
+    // Including after saving and loading again
+    achievements.save().unwrap();
+    let mut achievements = Achievements::load(&definitions, &dir.join("unlocked.toml")).unwrap();
+    achievements.select_rom(&rom);
+    assert!(achievements.update(&machine(0, 0).state()).is_empty());
+    assert!(achievements.update(&machine(10, 0).state()).is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Loads the script `source` into `chip8`, from a file of its own.
+fn script(name: &str, source: &str, chip8: &mut Chip8) -> Script {
+    let dir = temp_dir(name);
+    let path = dir.join("script.rhai");
+    fs::write(&path, source).unwrap();
+    let script = Script::load(&path, chip8).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    script
+}
+
+#[test]
+fn scripts_write_registers_memory_and_keys() {
+    let mut chip8 = Chip8::new(Database::builtin());
+    chip8
+        .load_rom(&assemble("LD V0, 1\nLD V0, 2\nend: JP end").unwrap())
+        .unwrap();
+    let source = "set(\"V1\", 5);\npoke(0x300, 7);\npress(3);\n\
+                  fn on_frame(frame) { set(\"V2\", get(\"V1\") + frame); }\n\
+                  fn on_instruction(pc, opcode) { if pc == 0x202 { poke(0x301, 300); } }";
+    let mut script = script("script", source, &mut chip8);
+    let mut keyboard = [false; 16];
+    keyboard[5] = true;
+    script.run_frame(&mut chip8, keyboard).unwrap();
+    let state = chip8.state();
+    assert_eq!(state.v[1..3], [5, 5]);
+    assert_eq!(state.memory[0x300], 7);
+    // Keys the script holds are added to the keyboard's
+    assert!(state.keys[3] && state.keys[5]);
+
+    // An error in on_instruction surfaces before the next frame, and the
+    // value it tried to write doesn't
+    chip8.run_frame(false).unwrap();
+    let err = script.run_frame(&mut chip8, keyboard).unwrap_err();
+    assert!(err.to_string().contains("invalid value 300"), "{}", err);
+    assert_eq!(chip8.state().memory[0x301], 0);
+}
+
+#[test]
+fn scripts_cant_write_values_that_dont_fit() {
+    for (source, error) in [
+        ("set(\"V0\", 256);", "invalid value 256"),
+        ("set(\"DT\", -1);", "invalid value -1"),
+        ("set(\"I\", 0x1000);", "invalid address 4096"),
+        ("poke(0x300, 300);", "invalid value 300"),
+        ("poke(0x1000, 1);", "invalid address 4096"),
+    ] {
+        let mut chip8 = Chip8::new(Database::builtin());
+        let mut script = script("values", source, &mut chip8);
+        let err = script.run_frame(&mut chip8, [false; 16]).unwrap_err();
+        assert!(err.to_string().contains(error), "{}: {}", source, err);
+    }
+}
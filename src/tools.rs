@@ -0,0 +1,93 @@
+use crate::chip8::{
+    assemble, detect_platform, disassemble, histogram, rom_hash, Database, Platform, RomConfig,
+};
+use crate::cli::{AsmArgs, DisasmArgs, InfoArgs};
+use std::{
+    error::Error,
+    fmt::Write as _,
+    fs,
+    io::{self, Write as _},
+};
+
+/// Width of the instruction column in listings, before the comments
+const LISTING_WIDTH: usize = 24;
+
+pub fn disasm(args: &DisasmArgs) -> Result<(), Box<dyn Error>> {
+    let rom = fs::read(&args.rom)?;
+    let platform = platform(Database::builtin().lookup(&rom), &rom);
+
+    let mut listing = format!(
+        "; {} ({} bytes, {})\n",
+        args.rom.display(),
+        rom.len(),
+        platform.name()
+    );
+    for line in disassemble(&rom, platform) {
+        let bytes: String = line
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        writeln!(
+            listing,
+            "    {:<width$}; 0x{:03X}: {}",
+            line.text,
+            line.address,
+            bytes,
+            width = LISTING_WIDTH
+        )?;
+    }
+
+    match &args.output {
+        Some(path) => fs::write(path, listing)?,
+        None => io::stdout().write_all(listing.as_bytes())?,
+    }
+    Ok(())
+}
+
+pub fn asm(args: &AsmArgs) -> Result<(), Box<dyn Error>> {
+    let source = fs::read_to_string(&args.source)?;
+    let rom = assemble(&source).map_err(|err| format!("{}: {}", args.source.display(), err))?;
+    fs::write(&args.output, &rom)?;
+    println!(
+        "[Asm] Wrote {} bytes to {}",
+        rom.len(),
+        args.output.display()
+    );
+    Ok(())
+}
+
+pub fn info(args: &InfoArgs) -> Result<(), Box<dyn Error>> {
+    let rom = fs::read(&args.rom)?;
+    let database = match &args.database {
+        Some(path) => Database::with_overrides(path)?,
+        None => Database::builtin(),
+    };
+    let config = database.lookup(&rom);
+
+    println!("File:     {}", args.rom.display());
+    println!("Size:     {} bytes", rom.len());
+    println!("SHA-1:    {}", rom_hash(&rom));
+    if let Some(title) = config.and_then(|config| config.title.as_deref()) {
+        println!("Title:    {}", title);
+    }
+    let platform = platform(config, &rom);
+    match config.and_then(|config| config.platform) {
+        Some(_) => println!("Platform: {} (from the database)", platform.name()),
+        None => println!("Platform: {} (detected)", platform.name()),
+    }
+
+    println!("Opcodes:");
+    for (pattern, count) in histogram(&rom, platform) {
+        println!("  {:>5}  {}", count, pattern);
+    }
+    Ok(())
+}
+
+/// The platform the database has for the ROM, or the one its opcodes
+/// suggest.
+fn platform(config: Option<&RomConfig>, rom: &[u8]) -> Platform {
+    config
+        .and_then(|config| config.platform)
+        .unwrap_or_else(|| detect_platform(rom))
+}